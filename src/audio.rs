@@ -1,61 +1,32 @@
-use {
-    crate::Error,
-    sdl2::{audio::{AudioCallback, AudioDevice, AudioSpecDesired}, Sdl},
-};
-
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32
-}
+pub trait Audio {
+    fn start(&mut self);
+    fn stop(&mut self);
+
+    fn toggle_mute(&mut self) {}
+
+    fn set_pattern(&mut self, _pattern: [u8; 16]) {}
+
+    fn set_pitch(&mut self, _pitch: u8) {}
+
+    fn click(&mut self) {}
 
-impl AudioCallback for SquareWave {
-    type Channel = f32;
-
-    fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+    // Silences the audio and drops anything the last program set up
+    fn reset(&mut self) {
+        self.stop();
     }
 }
 
-pub struct Audio {
-    device: AudioDevice<SquareWave>,
+// The pitch XO-CHIP programs start with, which plays patterns at 4000Hz
+pub const DEFAULT_PITCH: u8 = 64;
+
+pub fn playback_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
 }
 
-impl Audio {
-    pub fn new(sdl: &Sdl) -> Result<Audio, Error> {
-        let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
-            channels: Some(1),
-            samples: None,
-        };
-
-        let audio = sdl.audio()?;
-        let device = audio.open_playback(None, &desired_spec, |spec| {
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25
-            }
-        })?;
-
-        Ok(Audio {
-            device
-        })
-    }
+pub struct NullAudio;
 
-    pub fn start(&self) {
-        self.device.resume();
-    }
+impl Audio for NullAudio {
+    fn start(&mut self) {}
 
-    pub fn stop(&self) {
-        self.device.pause();
-    }
+    fn stop(&mut self) {}
 }
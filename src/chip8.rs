@@ -1,15 +1,40 @@
 use {
-    crate::{audio::Audio, cli::*, display::Display, input::Input, Error},
+    crate::{
+        audio::{self, Audio},
+        cli::*,
+        debugger::{self, Resume},
+        disassembler::{self, disassemble},
+        display::Display,
+        input::{Action, Input, Keypad},
+        launcher,
+        recording::Recorder,
+        replay::{InputRecorder, InputReplay},
+        state::MachineState,
+        Error,
+    },
     log::*,
     quark::BitIndex,
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    std::collections::{HashMap, HashSet, VecDeque},
     std::fs::File,
-    std::io::Read,
+    std::env,
+    std::fs,
+    std::io::{self, BufWriter, Read, Write},
+    std::path::{Path, PathBuf},
     std::thread,
-    std::time::Duration,
+    std::time::{Duration, Instant},
 };
 
-const PROGRAM_START: usize = 0x200;
-const STACK_START: usize = PROGRAM_START - 32;
+#[cfg(feature = "control")]
+use crate::control::{Control, Response};
+
+pub const PROGRAM_START: usize = 0x200;
+const MEMORY_SIZE: usize = 0x1000;
+const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+const STACK_SIZE: usize = 32;
+const STACK_START: usize = PROGRAM_START - STACK_SIZE;
+
+type Decoded = (u16, (u16, u16, u16, u16));
 
 pub struct Chip8 {
     v: [u8; 16],
@@ -19,136 +44,704 @@ pub struct Chip8 {
     at: u8,
     dt: u8,
     memory: Vec<u8>,
-    audio: Audio,
-    display: Display,
-    input: Input,
+    address_mask: usize,
+    load_address: usize,
+    font: Vec<u8>,
+    rom_dir: PathBuf,
+    audio: Box<dyn Audio>,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    min_beep: Duration,
+    beep_requested: bool,
+    display: Box<dyn Display>,
+    input: Box<dyn Input>,
+    clock_speed: u64,
+    turbo_factor: u32,
+    speed: f64,
+    quirks: Quirks,
+    xo_chip: bool,
+    ignore_sys: bool,
+    loop_detect: bool,
+    loop_pause: bool,
+    spin_state: Option<(usize, [u8; 16], usize)>,
+    spin_cycles: u64,
+    spin_reported: bool,
+    collision_beep: bool,
+    warn_reserved_writes: bool,
+    written: Option<Vec<u64>>,
+    decoded: Option<Vec<Option<Decoded>>>,
+    coverage: Option<Vec<u64>>,
+    rng: StdRng,
+    headless: bool,
+    max_cycles: Option<u64>,
     cycles: u64,
+    show_stats: bool,
+    debug_overlay: bool,
+    presents: u64,
+    timer_accumulator: Duration,
+    waiting_for_vblank: bool,
+    vblank: bool,
     halted: bool,
+    paused: bool,
+    breakpoints: HashSet<usize>,
+    skip_breakpoint: bool,
+    return_breakpoint: Option<(usize, usize)>,
+    #[cfg(feature = "control")]
+    control: Option<Control>,
+    quicksave: Option<Vec<u8>>,
+    rewind: VecDeque<MachineState>,
+    rewind_capacity: usize,
+    last_rewind_point: u64,
+    recorder: Option<Recorder>,
+    trace: Option<BufWriter<File>>,
+    input_recorder: Option<InputRecorder>,
+    input_replay: Option<InputReplay>,
+    flags: [u8; 8],
+    flags_changed: bool,
+    flags_path: Option<PathBuf>,
 }
 
-const CYCLES_PER_SECOND: u64 = 120;
-const CYCLE_RATE: Duration = Duration::from_nanos(1_000_000_000 / CYCLES_PER_SECOND);
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+const REWIND_INTERVAL: Duration = Duration::from_millis(100);
+const COVERAGE_BAR_WIDTH: u64 = 40;
+const SLOW_MOTION_SPEEDS: [f64; 3] = [0.5, 0.25, 0.1];
+const TIMER_RATE: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// A second at the default clock speed
+const SPIN_LOOP_CYCLES: u64 = 120;
 
 impl Chip8 {
-    pub fn new(config: &Config) -> Result<Chip8, Error> {
-        let sdl = sdl2::init()?;
+    pub fn new(
+        config: &Config,
+        audio: Box<dyn Audio>,
+        display: Box<dyn Display>,
+        input: Box<dyn Input>,
+    ) -> Result<Chip8, Error> {
+        let path = config.program.as_deref().ok_or_else(|| Error::InvalidOption("no program given".into()))?;
+        if path == Path::new("-") && config.control {
+            return Err(Error::InvalidOption("--control reads commands from stdin, so the program can't come from it".into()));
+        }
+        let program = read_program(path)?;
 
-        let audio = Audio::new(&sdl)?;
-        let display = Display::new(&sdl, &config)?;
-        let input = Input::new(&sdl, &config)?;
+        Chip8::with_program(config, &program, audio, display, input)
+    }
 
-        let mut memory = vec![0; 0x1000];
-        memory[0..][..FONT_DATA.len()].copy_from_slice(FONT_DATA);
+    pub fn with_program(
+        config: &Config,
+        program: &[u8],
+        audio: Box<dyn Audio>,
+        display: Box<dyn Display>,
+        mut input: Box<dyn Input>,
+    ) -> Result<Chip8, Error> {
+        if config.clock_speed == 0 {
+            return Err(Error::InvalidOption("clock speed must be greater than zero".into()));
+        }
+        if !(config.speed > 0.0 && config.speed.is_finite()) {
+            return Err(Error::InvalidOption("speed must be greater than zero".into()));
+        }
+        if config.scale == Some(0) {
+            return Err(Error::InvalidOption("scale must be greater than zero".into()));
+        }
 
-        let program = {
-            let mut file = File::open(&config.program)?;
-            let mut buffer = Vec::with_capacity(0x1000);
-            let size = file.read_to_end(&mut buffer)?;
-            info!(target: "cli", "read {} bytes from {}", size, config.program.display());
+        let font = match &config.font {
+            Some(path) => fs::read(path)?,
+            None => FONT_DATA.to_vec(),
+        };
+        if font.len() != FONT_DATA.len() {
+            return Err(Error::InvalidOption(format!(
+                "font must be {} bytes (16 digits of {} bytes), but it is {} bytes",
+                FONT_DATA.len(),
+                FONT_DIGIT_SIZE,
+                font.len(),
+            )));
+        }
 
-            buffer
+        let start = config.load_address;
+        let memory = load_memory(if config.xo_chip() { XO_CHIP_MEMORY_SIZE } else { MEMORY_SIZE }, start, &font, program)?;
+
+        let trace = match &config.trace {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
         };
-        memory[PROGRAM_START..][..program.len()].copy_from_slice(&program);
 
+        if (config.control || config.control_socket.is_some()) && !cfg!(feature = "control") {
+            return Err(Error::InvalidOption("--control needs chirp8 built with the `control` feature".into()));
+        }
+        if config.control && config.control_socket.is_some() {
+            return Err(Error::InvalidOption("--control and --control-socket can't be used together".into()));
+        }
+
+        let input_recorder = match &config.record_input {
+            Some(path) => {
+                input.keypad_mut().start_journal();
+                Some(InputRecorder::new(path)?)
+            }
+            None => None,
+        };
+        let input_replay = match &config.replay_input {
+            Some(path) => Some(InputReplay::load(path)?),
+            None => None,
+        };
+
+        let flags_path = if config.headless { None } else { flags_path() };
+        let flags = flags_path.as_deref().map(read_flags).unwrap_or_default();
+
+        let memory_len = memory.len();
         Ok(Chip8 {
             v: [0; 16],
             i: 0,
-            pc: PROGRAM_START,
+            pc: start,
             sp: STACK_START,
             at: 0,
             dt: 0,
             memory,
+            address_mask: memory_len - 1,
+            load_address: start,
+            font,
+            rom_dir: config.rom_dir.clone(),
             audio,
+            audio_pattern: None,
+            pitch: audio::DEFAULT_PITCH,
+            min_beep: Duration::from_millis(config.min_beep_ms),
+            beep_requested: false,
             display,
             input,
+            clock_speed: config.clock_speed,
+            turbo_factor: config.turbo_factor.max(1),
+            speed: config.speed,
+            quirks: config.quirks(),
+            xo_chip: config.xo_chip(),
+            ignore_sys: config.ignore_sys,
+            loop_detect: config.loop_detect || config.loop_pause,
+            loop_pause: config.loop_pause,
+            spin_state: None,
+            spin_cycles: 0,
+            spin_reported: false,
+            collision_beep: config.collision_beep,
+            warn_reserved_writes: config.warn_reserved_writes,
+            written: if config.warn_self_modifying { Some(vec![0; memory_len / 64]) } else { None },
+            decoded: if config.decode_cache { Some(vec![None; memory_len]) } else { None },
+            coverage: if config.coverage { Some(vec![0; 0x10000]) } else { None },
+            rng: match config.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            headless: config.headless,
+            max_cycles: config.max_cycles,
             cycles: 0,
+            show_stats: config.show_stats,
+            debug_overlay: config.debug_overlay,
+            presents: 0,
+            timer_accumulator: Duration::ZERO,
+            waiting_for_vblank: false,
+            vblank: false,
             halted: false,
+            paused: config.step,
+            breakpoints: config.breakpoints.iter().cloned().collect(),
+            skip_breakpoint: false,
+            return_breakpoint: None,
+            #[cfg(feature = "control")]
+            control: match &config.control_socket {
+                Some(address) => Some(Control::listen(address)?),
+                None if config.control => Some(Control::new()),
+                None => None,
+            },
+            quicksave: None,
+            rewind: VecDeque::new(),
+            rewind_capacity: (Duration::from_secs(config.rewind_seconds as u64).as_nanos() / REWIND_INTERVAL.as_nanos()) as usize,
+            last_rewind_point: 0,
+            recorder: config
+                .record
+                .clone()
+                .map(|path| Recorder::new(path, config.colors(), config.scale())),
+            trace,
+            input_recorder,
+            input_replay,
+            flags,
+            flags_changed: false,
+            flags_path,
         })
     }
 
     pub fn run(&mut self) -> Result<(), Error> {
-        info!(target: "exe", "starting run loop");
-        self.display.present()?;
-        loop {
-            self.input.process_pending_input();
-            if self.input.quit {
-                info!(target: "exe", "quit requested; halting");
+        // Every way out of the loop, errors included, goes through here so the
+        // recordings and flags get written out in full
+        let result = self.run_loop();
+        let finished = self.finish();
+
+        result.and(finished)
+    }
+
+    fn run_loop(&mut self) -> Result<(), Error> {
+        let cycle_rate = self.cycle_rate();
+
+        info!(target: "exe", "starting run loop at {} Hz", self.clock_speed);
+        self.present()?;
+        let mut stats_start = (Instant::now(), self.cycles, self.presents);
+        let mut last_tick = Instant::now();
+        let mut accumulator = Duration::ZERO;
+        let mut rewind_accumulator = REWIND_INTERVAL;
+        let mut last_overlay = Instant::now() - TIMER_RATE;
+        let mut beep_until = Instant::now();
+        'run: loop {
+            if self.show_stats && stats_start.0.elapsed() >= STATS_INTERVAL {
+                let (start, cycles, presents) = stats_start;
+                let seconds = start.elapsed().as_secs_f64();
+                let stats = format!(
+                    "{:.0} ips, {:.0} fps",
+                    self.cycles.saturating_sub(cycles) as f64 / seconds,
+                    self.presents.saturating_sub(presents) as f64 / seconds,
+                );
+                info!(target: "exe", "{}", stats);
+                self.display.show_stats(&stats);
+                stats_start = (Instant::now(), self.cycles, self.presents);
+            }
+
+            let mut step = false;
+            for action in self.input.process_pending_input() {
+                match action {
+                    Action::Quit => {
+                        info!(target: "exe", "quit requested; halting");
+                        break 'run;
+                    }
+                    Action::CloseWindow => {
+                        info!(target: "exe", "window closed; halting");
+                        break 'run;
+                    }
+                    Action::SaveState => {
+                        info!(target: "exe", "saving state at cycle {}", self.cycles);
+                        self.quicksave = Some(self.save_state());
+                    }
+                    Action::LoadState => match self.quicksave.take() {
+                        Some(state) => {
+                            info!(target: "exe", "restoring saved state");
+                            self.load_state(&state)?;
+                            self.quicksave = Some(state);
+                        }
+                        None => warn!(target: "exe", "no saved state to restore"),
+                    },
+                    Action::Step => step = true,
+                    Action::Screenshot => self.display.request_screenshot(),
+                    Action::ToggleMute => self.audio.toggle_mute(),
+                    Action::PrintRegisters => println!("{}", self.registers_string()),
+                    Action::CycleSpeed => {
+                        self.speed = SLOW_MOTION_SPEEDS.iter().copied().find(|&speed| speed < self.speed).unwrap_or(1.0);
+                        info!(target: "exe", "running at {}x speed", self.speed);
+                    }
+                    Action::ToggleOverlay => {
+                        self.debug_overlay = !self.debug_overlay;
+                        if !self.debug_overlay {
+                            self.display.show_overlay(None);
+                        }
+                    }
+                    Action::Redraw => self.display.framebuffer_mut().invalidate(),
+                    Action::OpenProgram => {
+                        if let Err(err) = self.open_program() {
+                            warn!(target: "exe", "couldn't open program: {}", err);
+                        }
+                    }
+                }
+            }
+
+            if let Resume::Quit = self.process_control()? {
+                info!(target: "exe", "control quit; halting");
                 break;
             }
 
-            self.step()?;
-            self.cycles += 1;
+            if let Some(recorder) = &mut self.input_recorder {
+                recorder.record(self.cycles, self.input.keypad_mut())?;
+            }
 
-            self.update_timers();
+            let turbo = self.input.turbo_held();
+            let rewinding = self.input.rewind_held();
+            let speed = if turbo { self.turbo_factor as f64 } else { 1.0 } * self.speed;
+            let now = Instant::now();
+            let elapsed = now - last_tick;
+            last_tick = now;
+
+            // Run however many cycles are due since the last tick, capping the
+            // catch-up so a stall doesn't turn into a burst of instructions
+            let due = if rewinding {
+                accumulator = Duration::ZERO;
+                rewind_accumulator += elapsed;
+                while rewind_accumulator >= REWIND_INTERVAL {
+                    rewind_accumulator -= REWIND_INTERVAL;
+                    self.rewind()?;
+                }
+                0
+            } else if self.paused {
+                rewind_accumulator = REWIND_INTERVAL;
+                accumulator = Duration::ZERO;
+                if step { 1 } else { 0 }
+            } else {
+                rewind_accumulator = REWIND_INTERVAL;
+                accumulator = (accumulator + elapsed.mul_f64(speed)).min(MAX_CATCH_UP.mul_f64(speed.max(1.0)));
+                let due = accumulator.as_nanos() / cycle_rate.as_nanos();
+                accumulator = Duration::from_nanos((accumulator.as_nanos() % cycle_rate.as_nanos()) as u64);
+                due
+            };
+
+            for _ in 0..due {
+                if self.max_cycles.is_some_and(|max| self.cycles >= max) {
+                    info!(target: "exe", "reached {} cycles; halting", self.cycles);
+                    break 'run;
+                }
+
+                if !self.skip_breakpoint && self.at_breakpoint() {
+                    self.return_breakpoint = None;
+                    self.audio.stop();
+                    if self.break_to_control()? {
+                        self.skip_breakpoint = true;
+                        break;
+                    }
+                    match debugger::prompt(self)? {
+                        Resume::Continue => self.skip_breakpoint = true,
+                        Resume::Quit => break 'run,
+                    }
+                    last_tick = Instant::now();
+                    accumulator = Duration::ZERO;
+                    break;
+                }
+                self.skip_breakpoint = false;
+
+                let pc = self.pc;
+                self.cycle()?;
+                if self.paused {
+                    if let Some(opcode) = self.opcode_at(pc) {
+                        println!("{}", disassemble(opcode, pc));
+                    }
+                    println!("{}", self.registers_string());
+                }
+
+                if self.halted && self.headless {
+                    info!(target: "exe", "machine halted; stopping");
+                    break 'run;
+                }
+            }
+
+            if !rewinding {
+                self.record_rewind_point(cycle_rate);
+            }
+
+            // Registers change every cycle, so refresh at the frame rate rather than redrawing for each one
+            if self.debug_overlay && last_overlay.elapsed() >= TIMER_RATE {
+                let registers = self.registers_string();
+                self.display.show_overlay(Some(&registers));
+                last_overlay = Instant::now();
+            }
 
             if self.display.needs_presenting() {
-                self.display.present()?;
+                self.present()?;
             }
 
-            if self.at == 0 {
+            // A short sound timer can run out between two passes of the loop, so hold the
+            // beeper on for the minimum length from when the program set it
+            if self.beep_requested {
+                self.beep_requested = false;
+                beep_until = Instant::now() + self.min_beep;
+            }
+            let beep_held = Instant::now() < beep_until;
+            if self.paused || (self.at == 0 && !beep_held) || turbo || rewinding {
                 self.audio.stop();
             } else {
                 self.audio.start();
             }
 
-            thread::sleep(CYCLE_RATE);
+            let until_next_cycle = cycle_rate.saturating_sub(accumulator).div_f64(speed);
+            thread::sleep(until_next_cycle.min(TIMER_RATE));
         }
 
         Ok(())
     }
-}
 
-impl Chip8 {
-    fn step(&mut self) -> Result<(), Error> {
+    fn finish(&mut self) -> Result<(), Error> {
+        self.audio.stop();
+
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish(self.display.framebuffer())?;
+        }
+
+        if let Some(trace) = &mut self.trace {
+            trace.flush()?;
+        }
+
+        if let Some(recorder) = &mut self.input_recorder {
+            recorder.flush()?;
+        }
+
+        if let (true, Some(path)) = (self.flags_changed, &self.flags_path) {
+            info!(target: "exe", "saving flags to {}", path.display());
+            if let Err(e) = fs::write(path, self.flags) {
+                warn!(target: "exe", "couldn't save flags to {}: {}", path.display(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<(), Error> {
+        let end = self.cycles + cycles;
+        while self.cycles < end && !self.halted {
+            self.cycle()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn benchmark(&mut self, duration: Duration) -> Result<(u64, Duration), Error> {
+        info!(target: "exe", "benchmarking for {:?}", duration);
+        let start = Instant::now();
+        let cycles = self.cycles;
+        loop {
+            if self.max_cycles.is_some_and(|max| self.cycles >= max) {
+                break;
+            }
+
+            if self.halted {
+                warn!(target: "exe", "machine halted after {} cycles; stopping benchmark", self.cycles);
+                break;
+            }
+
+            self.cycle()?;
+
+            // Checking the clock is slow enough to skew the result if done every cycle
+            if self.cycles.is_multiple_of(1024) && start.elapsed() >= duration {
+                break;
+            }
+        }
+
+        if let Some(trace) = &mut self.trace {
+            trace.flush()?;
+        }
+
+        Ok((self.cycles - cycles, start.elapsed()))
+    }
+
+    #[cfg(feature = "control")]
+    fn process_control(&mut self) -> Result<Resume, Error> {
+        match self.control.take() {
+            Some(mut control) => {
+                let resume = control.process(self);
+                self.control = Some(control);
+                resume
+            }
+            None => Ok(Resume::Continue),
+        }
+    }
+
+    #[cfg(not(feature = "control"))]
+    fn process_control(&mut self) -> Result<Resume, Error> {
+        Ok(Resume::Continue)
+    }
+
+    // Under remote control a breakpoint pauses and reports instead of prompting
+    #[cfg(feature = "control")]
+    fn break_to_control(&mut self) -> Result<bool, Error> {
+        let pc = self.pc;
+        match &mut self.control {
+            Some(control) => control.respond(&Response::Break { pc })?,
+            None => return Ok(false),
+        }
+
+        self.paused = true;
+        Ok(true)
+    }
+
+    #[cfg(not(feature = "control"))]
+    fn break_to_control(&mut self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn open_program(&mut self) -> Result<(), Error> {
+        info!(target: "exe", "choosing a program to open in the terminal");
+        self.audio.stop();
+        if let Some(path) = launcher::choose_program(&self.rom_dir)? {
+            let program = read_program(&path)?;
+            self.load_program(&program)?;
+            info!(target: "exe", "running {}", path.display());
+        }
+
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), Error> {
+        self.display.present()?;
+        self.presents += 1;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.capture(self.display.framebuffer());
+        }
+
+        Ok(())
+    }
+
+    fn record_rewind_point(&mut self, cycle_rate: Duration) {
+        let interval = (REWIND_INTERVAL.as_nanos() / cycle_rate.as_nanos()).max(1) as u64;
+        if self.rewind_capacity == 0 || self.cycles.saturating_sub(self.last_rewind_point) < interval {
+            return;
+        }
+
+        if self.rewind.len() == self.rewind_capacity {
+            self.rewind.pop_front();
+        }
+        self.rewind.push_back(self.machine_state());
+        self.last_rewind_point = self.cycles;
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        match self.rewind.pop_back() {
+            Some(state) => {
+                trace!(target: "exe", "rewinding to cycle {}", state.cycles);
+                self.restore_machine_state(state)?;
+                self.last_rewind_point = self.cycles;
+            }
+            None => debug!(target: "exe", "no more states to rewind to"),
+        }
+
+        Ok(())
+    }
+
+    pub fn cycle(&mut self) -> Result<(), Error> {
+        let was_halted = self.halted;
+        if let Some(replay) = &mut self.input_replay {
+            replay.advance(self.cycles);
+        }
+
+        let before = (self.pc, self.v, self.i, self.sp, self.dt, self.at);
+        self.step()?;
+        if !was_halted && self.trace.is_some() {
+            self.write_trace(before)?;
+        }
+        if self.halted && !was_halted {
+            info!(target: "exe", "machine halted after {} cycles", self.cycles);
+        }
+        self.cycles += 1;
+        self.update_timers(self.cycle_rate());
+
+        Ok(())
+    }
+
+    fn write_trace(&mut self, before: (usize, [u8; 16], usize, usize, u8, u8)) -> Result<(), Error> {
+        let (pc, v, i, sp, dt, at) = before;
+        let opcode = self.opcode_at(pc).unwrap_or_default();
+
+        let mut line = format!("{:<28}", disassemble(opcode, pc));
+        for (index, (old, new)) in v.iter().zip(self.v.iter()).enumerate() {
+            if old != new {
+                line.push_str(&format!(" v{:x}={:02x}", index, new));
+            }
+        }
+        if i != self.i {
+            line.push_str(&format!(" i={:03x}", self.i));
+        }
+        if sp != self.sp {
+            line.push_str(&format!(" sp={:03x}", self.sp));
+        }
+        if dt != self.dt {
+            line.push_str(&format!(" dt={:02x}", self.dt));
+        }
+        if at != self.at {
+            line.push_str(&format!(" st={:02x}", self.at));
+        }
+
+        if let Some(trace) = &mut self.trace {
+            writeln!(trace, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<(), Error> {
         if self.halted {
             return Ok(());
         }
 
         let pc = self.pc;
+        let (opcode, nibbles) = match self.fetch(pc) {
+            Some(decoded) => decoded,
+            None => {
+                error!(target: "asm", "{:03x}: program counter ran past the end of memory", pc);
+                self.halted = true;
+                return Ok(());
+            }
+        };
         self.pc += 2;
 
-        let opcode = u16::from_be_bytes([self.memory[pc], self.memory[pc + 1]]);
-        match (
-            opcode.bits(12..16),
-            opcode.bits(8..12),
-            opcode.bits(4..8),
-            opcode.bits(0..4),
-        ) {
+        if let Some(written) = &mut self.written {
+            // Clear the marks so a loop over modified code only warns once per write
+            let mut modified = false;
+            for address in pc..pc + 2 {
+                let bit = 1 << (address % 64);
+                modified |= written[address / 64] & bit != 0;
+                written[address / 64] &= !bit;
+            }
+            if modified {
+                warn!(target: "asm", "{}; executing code the program wrote", disassemble(opcode, pc));
+            }
+        }
+
+        debug!(target: "asm", "{}", disassemble(opcode, pc));
+        if let Some(coverage) = &mut self.coverage {
+            coverage[opcode as usize] += 1;
+        }
+        match nibbles {
             (0x0, 0x0, 0xe, 0x0) => {
-                debug!(target: "asm", "{:03x}: [{:04x}] cls", pc, opcode);
-                self.display.clear_screen()?;
+                self.display.clear_screen();
             }
             (0x0, 0x0, 0xe, 0xe) => {
-                debug!(target: "asm", "{:03x}: [{:04x}] ret", pc, opcode);
-                let address = u16::from_be_bytes([self.memory[self.sp], self.memory[self.sp + 1]]);
+                if self.sp <= STACK_START {
+                    error!(target: "asm", "{:03x}: [{:04x}] stack underflow", pc, opcode);
+                    self.halted = true;
+                    return Ok(());
+                }
+
                 self.sp -= 2;
+                let address = u16::from_be_bytes([self.memory[self.sp], self.memory[self.sp + 1]]);
                 self.pc = address as usize;
             }
+            (0x0, 0x0, 0xc, _) => {
+                let rows = opcode.bits(0..4) as usize;
+                self.display.scroll_down(rows);
+            }
+            (0x0, 0x0, 0xf, 0xb) => {
+                self.display.scroll_right(4);
+            }
+            (0x0, 0x0, 0xf, 0xc) => {
+                self.display.scroll_left(4);
+            }
+            (0x0, ..) if self.ignore_sys => {
+                warn!(target: "asm", "{}; ignoring", disassemble(opcode, pc));
+            }
             (0x0, ..) => {
-                let address = opcode.bits(0..12);
-                error!(target: "asm", "{:03x}: [{:04x}] sys {:03x}", pc, opcode, address);
+                error!(target: "asm", "{}", disassemble(opcode, pc));
                 self.halted = true;
                 return Ok(());
             }
             (0x1, ..) => {
                 let address = opcode.bits(0..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] jp {:03x}", pc, opcode, address);
+                if address == pc && self.loop_detect {
+                    self.detect_spin_loop(opcode, pc);
+                }
                 self.pc = address;
                 return Ok(());
             }
             (0x2, ..) => {
                 let address = opcode.bits(0..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] call {:03x}", pc, opcode, address);
-                self.sp += 2;
+                if self.sp >= STACK_START + STACK_SIZE {
+                    error!(target: "asm", "{:03x}: [{:04x}] stack overflow", pc, opcode);
+                    self.halted = true;
+                    return Ok(());
+                }
+
                 let bytes = (self.pc as u16).to_be_bytes();
+                self.invalidate_decoded(self.sp, 2);
                 self.memory[self.sp] = bytes[0];
                 self.memory[self.sp + 1] = bytes[1];
+                self.sp += 2;
                 self.pc = address;
             }
             (0x3, ..) => {
                 let x = opcode.bits(8..12) as usize;
                 let value = opcode.bits(0..8) as u8;
-                debug!(target: "asm", "{:03x}: [{:04x}] se v{:1x}, {:02x}", pc, opcode, x, value);
                 if self.v[x] == value {
                     self.pc += 2;
                 }
@@ -156,7 +749,6 @@ impl Chip8 {
             (0x4, ..) => {
                 let x = opcode.bits(8..12) as usize;
                 let value = opcode.bits(0..8) as u8;
-                debug!(target: "asm", "{:03x}: [{:04x}] sne v{:1x}, {:02x}", pc, opcode, x, value);
                 if self.v[x] != value {
                     self.pc += 2;
                 }
@@ -164,52 +756,69 @@ impl Chip8 {
             (0x5, _, _, 0x0) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] se v{:1x}, v{:1x}", pc, opcode, x, y);
                 if self.v[x] == self.v[y] {
                     self.pc += 2;
                 }
             }
+            (0x5, _, _, 0x2) => {
+                let x = opcode.bits(8..12) as usize;
+                let y = opcode.bits(4..8) as usize;
+                self.note_memory_write(pc, opcode, x.max(y) - x.min(y) + 1);
+                for (offset, register) in register_range(x, y).enumerate() {
+                    let address = self.i_address(offset);
+                    self.memory[address] = self.v[register];
+                }
+            }
+            (0x5, _, _, 0x3) => {
+                let x = opcode.bits(8..12) as usize;
+                let y = opcode.bits(4..8) as usize;
+                for (offset, register) in register_range(x, y).enumerate() {
+                    self.v[register] = self.memory[self.i_address(offset)];
+                }
+            }
             (0x6, ..) => {
                 let x = opcode.bits(8..12) as usize;
                 let value = opcode.bits(0..8) as u8;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld v{:1x}, {:02x}", pc, opcode, x, value);
                 self.v[x] = value;
             }
             (0x7, ..) => {
                 let x = opcode.bits(8..12) as usize;
                 let value = opcode.bits(0..8) as u8;
-                debug!(target: "asm", "{:03x}: [{:04x}] add v{:1x}, {:02x}", pc, opcode, x, value);
                 let value = self.v[x] as u16 + value as u16;
                 self.v[x] = (value % 256) as u8;
             }
             (0x8, _, _, 0x0) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld v{:1x}, v{:1x}", pc, opcode, x, y);
                 self.v[x] = self.v[y];
             }
             (0x8, _, _, 0x1) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] or v{:1x}, v{:1x}", pc, opcode, x, y);
                 self.v[x] = self.v[x] | self.v[y];
+                if self.quirks.logic {
+                    self.v[15] = 0;
+                }
             }
             (0x8, _, _, 0x2) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] and v{:1x}, v{:1x}", pc, opcode, x, y);
                 self.v[x] = self.v[x] & self.v[y];
+                if self.quirks.logic {
+                    self.v[15] = 0;
+                }
             }
             (0x8, _, _, 0x3) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] xor v{:1x}, v{:1x}", pc, opcode, x, y);
                 self.v[x] = self.v[x] ^ self.v[y];
+                if self.quirks.logic {
+                    self.v[15] = 0;
+                }
             }
             (0x8, _, _, 0x4) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] add v{:1x}, v{:1x}", pc, opcode, x, y);
                 let (value, overflow) = self.v[x].overflowing_add(self.v[y]);
                 self.v[x] = value;
                 self.v[15] = if overflow { 1 } else { 0 };
@@ -217,7 +826,6 @@ impl Chip8 {
             (0x8, _, _, 0x5) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] sub v{:1x}, v{:1x}", pc, opcode, x, y);
                 let (value, borrow) = self.v[x].overflowing_sub(self.v[y]);
                 self.v[x] = value;
                 self.v[15] = if !borrow { 1 } else { 0 };
@@ -225,14 +833,20 @@ impl Chip8 {
             (0x8, _, _, 0x6) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] shr v{:1x}, v{:1x}", pc, opcode, x, y);
-                self.v[15] = self.v[x] & 1;
-                self.v[x] >>= 1;
+                // The COSMAC VIP shifted vy into vx, but most later interpreters
+                // shift vx in place and leave the y operand unused
+                let source = match self.quirks.shift {
+                    ShiftQuirk::Vx => x,
+                    ShiftQuirk::Vy => y,
+                };
+                trace!(target: "asm", "shifting v{:x} right into v{:x}", source, x);
+                let value = self.v[source];
+                self.v[x] = value >> 1;
+                self.v[15] = value & 1;
             }
             (0x8, _, _, 0x7) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] subn v{:1x}, v{:1x}", pc, opcode, x, y);
                 let (value, borrow) = self.v[y].overflowing_sub(self.v[x]);
                 self.v[x] = value;
                 self.v[15] = if !borrow { 1 } else { 0 };
@@ -240,45 +854,74 @@ impl Chip8 {
             (0x8, _, _, 0xe) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] shl v{:1x}, v{:1x}", pc, opcode, x, y);
-                self.v[15] = self.v[x] & 0x80;
-                self.v[x] <<= 1;
+                let source = match self.quirks.shift {
+                    ShiftQuirk::Vx => x,
+                    ShiftQuirk::Vy => y,
+                };
+                trace!(target: "asm", "shifting v{:x} left into v{:x}", source, x);
+                let value = self.v[source];
+                self.v[x] = value << 1;
+                self.v[15] = (value & 0x80) >> 7;
             }
             (0x9, _, _, 0x0) => {
                 let x = opcode.bits(8..12) as usize;
                 let y = opcode.bits(4..8) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] sne v{:1x}, v{:1x}", pc, opcode, x, y);
                 if self.v[x] != self.v[y] {
                     self.pc += 2;
                 }
             }
             (0xa, ..) => {
                 let address = opcode.bits(0..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld i, {:03x}", pc, opcode, address);
                 self.i = address;
             }
             (0xb, ..) => {
                 let address = opcode.bits(0..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] jp v0, {:03x}", pc, opcode, address);
-                let address = self.v[0] as usize + address;
+                let register = match self.quirks.jump {
+                    JumpQuirk::V0 => 0,
+                    JumpQuirk::Vx => (address >> 8) & 0xf,
+                };
+                let address = (self.v[register] as usize + address) & self.address_mask;
                 self.pc = address;
             }
             (0xc, ..) => {
                 let x = opcode.bits(8..12) as usize;
                 let mask = opcode.bits(0..8) as u8;
-                debug!(target: "asm", "{:03x}: [{:04x}] rnd v{:1x}, {:02x}", pc, opcode, x, mask);
-                let byte: u8 = rand::random();
+                let byte: u8 = self.rng.gen();
                 self.v[x] = byte & mask;
             }
             (0xd, ..) => {
+                // The COSMAC VIP drew sprites during the vertical blank, so a draw
+                // waits for the next timer tick like `ld vx, k` waits for a key
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        self.waiting_for_vblank = true;
+                        self.pc = pc;
+                        return Ok(());
+                    }
+                    self.waiting_for_vblank = false;
+                    self.vblank = false;
+                }
+
                 let vx = opcode.bits(8..12) as usize;
                 let vy = opcode.bits(4..8) as usize;
                 let n = opcode.bits(0..4) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] drw v{:1x}, v{:1x}, {:1x}", pc, opcode, vx, vy, n);
-                let sprite = &self.memory[self.i..][..n];
+                if n == 0 {
+                    // SCHIP draws a 16x16 sprite for n = 0, which isn't supported yet
+                    warn!(target: "asm", "{}: empty sprite; this may be an SCHIP program", disassemble(opcode, pc));
+                }
+                let planes = self.display.framebuffer().planes.count_ones() as usize;
+                // Up to 15 rows for each of the two planes
+                let mut sprite = [0; 30];
+                let sprite = &mut sprite[..n * planes];
+                for (offset, byte) in sprite.iter_mut().enumerate() {
+                    *byte = self.memory[self.i_address(offset)];
+                }
                 let x = self.v[vx];
                 let y = self.v[vy];
-                let toggled_off = self.display.draw_sprite(sprite, x, y)?;
+                let toggled_off = self.display.draw_sprite(sprite, x, y, self.quirks.wrap);
+                if toggled_off && self.collision_beep {
+                    self.audio.click();
+                }
                 if toggled_off {
                     self.v[15] = 1;
                 } else {
@@ -287,84 +930,137 @@ impl Chip8 {
             }
             (0xe, _, 0x9, 0xe) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] skp v{:1x}", pc, opcode, x);
-                if self.input.is_key_pressed(self.v[x]) {
+                let key = self.v[x];
+                if self.keypad().is_key_pressed(key) {
                     self.pc += 2;
                 }
             }
             (0xe, _, 0xa, 0x1) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] sknp v{:1x}", pc, opcode, x);
-                if !self.input.is_key_pressed(self.v[x]) {
+                let key = self.v[x];
+                if !self.keypad().is_key_pressed(key) {
                     self.pc += 2;
                 }
             }
+            (0xf, 0x0, 0x0, 0x0) if self.xo_chip => {
+                let address = match self.opcode_at(self.pc) {
+                    Some(address) => address,
+                    None => {
+                        error!(target: "asm", "{:03x}: [{:04x}] address runs past the end of memory", pc, opcode);
+                        self.halted = true;
+                        return Ok(());
+                    }
+                };
+                self.pc += 2;
+                self.i = address as usize;
+            }
+            (0xf, 0x0, 0x0, 0x2) => {
+                let mut pattern = [0; 16];
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.memory[self.i_address(offset)];
+                }
+                self.audio_pattern = Some(pattern);
+                self.audio.set_pattern(pattern);
+            }
+            (0xf, _, 0x0, 0x1) => {
+                let planes = opcode.bits(8..12) as u8;
+                self.display.select_planes(planes);
+            }
             (0xf, _, 0x0, 0x7) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld v{:1x}, dt", pc, opcode, x);
                 self.v[x] = self.dt;
             }
             (0xf, _, 0x0, 0xa) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld v{:1x}, k", pc, opcode, x);
-                let value = self.input.wait_for_input();
-                self.v[x] = value;
+                match self.keypad().wait_for_input() {
+                    Some(value) => self.v[x] = value,
+                    None => {
+                        self.pc = pc;
+                        // It runs again next cycle, so only the pass that gets a key counts
+                        if let Some(coverage) = &mut self.coverage {
+                            coverage[opcode as usize] -= 1;
+                        }
+                    }
+                }
             }
             (0xf, _, 0x1, 0x5) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld dt, v{:1x}", pc, opcode, x);
                 self.dt = self.v[x];
             }
             (0xf, _, 0x1, 0x8) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld st, v{:1x}", pc, opcode, x);
                 self.at = self.v[x];
+                self.beep_requested = self.at > 0;
             }
             (0xf, _, 0x1, 0xe) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] add i, v{:1x}", pc, opcode, x);
-                self.i = self.i + self.v[x] as usize;
+                let address = self.i + self.v[x] as usize;
+                if self.quirks.fx1e_overflow_flag {
+                    self.v[15] = if address > self.address_mask { 1 } else { 0 };
+                }
+                self.i = address & self.address_mask;
             }
             (0xf, _, 0x2, 0x9) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld f, v{:1x}", pc, opcode, x);
                 let digit = self.v[x] as usize;
                 self.i = FONT_DATA_START + digit * FONT_DIGIT_SIZE;
             }
+            (0xf, _, 0x3, 0x0) => {
+                let x = opcode.bits(8..12) as usize;
+                let digit = self.v[x] as usize;
+                self.i = LARGE_FONT_DATA_START + digit * LARGE_FONT_DIGIT_SIZE;
+            }
+            (0xf, _, 0x3, 0xa) => {
+                let x = opcode.bits(8..12) as usize;
+                self.pitch = self.v[x];
+                self.audio.set_pitch(self.pitch);
+            }
             (0xf, _, 0x3, 0x3) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld b, v{:1x}", pc, opcode, x);
-                let mut value = self.v[x as usize];
+                let mut value = self.v[x];
                 let ones = value % 10;
                 value /= 10;
                 let tens = value % 10;
                 value /= 10;
                 let hundreds = value;
 
-                self.memory[self.i] = hundreds;
-                self.memory[self.i + 1] = tens;
-                self.memory[self.i + 2] = ones;
+                self.note_memory_write(pc, opcode, 3);
+                for (offset, digit) in [hundreds, tens, ones].iter().enumerate() {
+                    let address = self.i_address(offset);
+                    self.memory[address] = *digit;
+                }
             }
             (0xf, _, 0x5, 0x5) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld [i], v{:1x}", pc, opcode, x);
+                self.note_memory_write(pc, opcode, x + 1);
                 for i in 0..=x {
-                    let value = self.v[i];
-                    self.memory[self.i] = value;
-                    self.i += 1;
+                    let address = self.i_address(i);
+                    self.memory[address] = self.v[i];
+                }
+                if self.quirks.load_store == MemoryQuirk::Increment {
+                    self.i = self.i_address(x + 1);
                 }
             }
             (0xf, _, 0x6, 0x5) => {
                 let x = opcode.bits(8..12) as usize;
-                debug!(target: "asm", "{:03x}: [{:04x}] ld v{:1x}, [i]", pc, opcode, x);
                 for i in 0..=x {
-                    let value = self.memory[self.i];
-                    self.v[i] = value;
-                    self.i += 1;
+                    self.v[i] = self.memory[self.i_address(i)];
+                }
+                if self.quirks.load_store == MemoryQuirk::Increment {
+                    self.i = self.i_address(x + 1);
                 }
             }
+            (0xf, _, 0x7, 0x5) => {
+                let x = opcode.bits(8..12).min(7) as usize;
+                self.flags[..=x].copy_from_slice(&self.v[..=x]);
+                self.flags_changed = true;
+            }
+            (0xf, _, 0x8, 0x5) => {
+                let x = opcode.bits(8..12).min(7) as usize;
+                self.v[..=x].copy_from_slice(&self.flags[..=x]);
+            }
             _ => {
-                error!(target: "asm", "{:03x}: [{:04x}] unknown instruction", pc, opcode);
+                error!(target: "asm", "{}", disassemble(opcode, pc));
                 self.halted = true;
                 return Ok(());
             }
@@ -373,22 +1069,430 @@ impl Chip8 {
         Ok(())
     }
 
-    fn update_timers(&mut self) {
-        if self.cycles < CYCLES_PER_SECOND / 60 {
+    pub fn save_state(&self) -> Vec<u8> {
+        self.machine_state().to_bytes()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.restore_machine_state(MachineState::from_bytes(state)?)
+    }
+
+    pub fn load_program(&mut self, program: &[u8]) -> Result<(), Error> {
+        self.memory = load_memory(self.memory.len(), self.load_address, &self.font, program)?;
+        self.v = [0; 16];
+        self.i = 0;
+        self.pc = self.load_address;
+        self.sp = STACK_START;
+        self.at = 0;
+        self.dt = 0;
+        self.beep_requested = false;
+        self.halted = false;
+        self.spin_state = None;
+        self.spin_cycles = 0;
+        self.spin_reported = false;
+        self.waiting_for_vblank = false;
+        self.cycles = 0;
+        self.quicksave = None;
+        self.rewind.clear();
+        if let Some(written) = &mut self.written {
+            written.iter_mut().for_each(|entry| *entry = 0);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.iter_mut().for_each(|entry| *entry = 0);
+        }
+        if let Some(decoded) = &mut self.decoded {
+            decoded.iter_mut().for_each(|entry| *entry = None);
+        }
+
+        self.audio_pattern = None;
+        self.pitch = audio::DEFAULT_PITCH;
+        self.audio.reset();
+        self.display.select_planes(0b11);
+        self.display.clear_screen();
+        self.display.select_planes(0b01);
+
+        Ok(())
+    }
+
+    pub fn machine_state(&self) -> MachineState {
+        MachineState {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            delay_timer: self.dt,
+            sound_timer: self.at,
+            halted: self.halted,
+            cycles: self.cycles,
+            memory: self.memory.clone(),
+            pixels: self.display.framebuffer().pixels(),
+            planes: self.display.framebuffer().planes,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+        }
+    }
+
+    pub fn restore_machine_state(&mut self, state: MachineState) -> Result<(), Error> {
+        if state.memory.len() != self.memory.len() {
+            return Err(Error::InvalidSaveState(format!(
+                "machine state has {} bytes of memory, expected {}",
+                state.memory.len(),
+                self.memory.len(),
+            )));
+        }
+        if state.i >= self.memory.len() || state.pc >= self.memory.len() {
+            return Err(Error::InvalidSaveState(format!(
+                "machine state has i={:03x} pc={:03x}, past the end of memory",
+                state.i, state.pc,
+            )));
+        }
+        if !(STACK_START..=STACK_START + STACK_SIZE).contains(&state.sp) || (state.sp - STACK_START) & 1 != 0 {
+            return Err(Error::InvalidSaveState(format!("machine state has sp={:03x}, outside the stack", state.sp)));
+        }
+
+        let framebuffer = self.display.framebuffer_mut();
+        let expected = framebuffer.w * framebuffer.h;
+        if state.pixels.len() != expected {
+            return Err(Error::InvalidSaveState(format!(
+                "machine state has {} pixels, expected {}",
+                state.pixels.len(),
+                expected,
+            )));
+        }
+
+        framebuffer.set_pixels(&state.pixels);
+        self.display.select_planes(state.planes);
+
+        // Resetting is the only way to drop a pattern, and it puts back the default pitch
+        if self.audio_pattern.is_some() && state.audio_pattern.is_none() {
+            self.audio.reset();
+        }
+        if let Some(pattern) = state.audio_pattern {
+            self.audio.set_pattern(pattern);
+        }
+        self.audio.set_pitch(state.pitch);
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+
+        self.v = state.v;
+        self.i = state.i;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.dt = state.delay_timer;
+        self.at = state.sound_timer;
+        self.halted = state.halted;
+        self.cycles = state.cycles;
+        self.memory = state.memory;
+        if let Some(decoded) = &mut self.decoded {
+            decoded.iter_mut().for_each(|entry| *entry = None);
+        }
+
+        Ok(())
+    }
+
+    pub fn registers_string(&self) -> String {
+        let mut string = String::new();
+        for (index, value) in self.v.iter().enumerate() {
+            let separator = if index % 8 == 7 { '\n' } else { ' ' };
+            string.push_str(&format!("v{:x}={:02x}{}", index, value, separator));
+        }
+        string.push_str(&format!(
+            "i={:03x} pc={:03x} sp={:03x} dt={:02x} st={:02x}",
+            self.i, self.pc, self.sp, self.dt, self.at,
+        ));
+
+        string
+    }
+
+    pub fn coverage_report(&self) -> Option<String> {
+        let coverage = self.coverage.as_ref()?;
+        let mut counts = HashMap::new();
+        for (opcode, &count) in coverage.iter().enumerate().filter(|(_, &count)| count > 0) {
+            *counts.entry(disassembler::decode(opcode as u16).mnemonic).or_insert(0) += count;
+        }
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let most = counts.first().map(|&(_, count)| count).unwrap_or(0);
+        let mut string = String::new();
+        for (mnemonic, count) in counts {
+            let bar = "#".repeat(((count * COVERAGE_BAR_WIDTH) / most).max(1) as usize);
+            string.push_str(&format!("{:>10} {:<16} {}\n", count, mnemonic, bar));
+        }
+
+        Some(string)
+    }
+
+    pub fn dump_memory(&self, start: usize, len: usize) -> String {
+        let end = start.saturating_add(len).min(self.memory.len());
+        let start = start.min(end);
+
+        let mut string = String::new();
+        for (row, bytes) in self.memory[start..end].chunks(16).enumerate() {
+            let hex: Vec<_> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = bytes
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            string.push_str(&format!("{:03x}: {:<47}  |{}|\n", start + row * 16, hex.join(" "), ascii));
+        }
+
+        string
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    // Breaks once the `call` at pc returns, which is when execution reaches the
+    // next instruction with the stack back where it is now (so recursive calls
+    // passing through the same address don't count)
+    pub fn break_on_return(&mut self) {
+        self.return_breakpoint = Some((self.pc + 2, self.sp));
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        self.breakpoints.contains(&self.pc)
+            || self.return_breakpoint.is_some_and(|(address, sp)| self.pc == address && self.sp <= sp)
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.at
+    }
+
+    pub fn display(&self) -> &dyn Display {
+        self.display.as_ref()
+    }
+
+    pub fn keypad_mut(&mut self) -> &mut Keypad {
+        self.input.keypad_mut()
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn opcode_at(&self, address: usize) -> Option<u16> {
+        let bytes = self.memory.get(address..address + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+}
+
+impl Chip8 {
+    fn cycle_rate(&self) -> Duration {
+        Duration::from_nanos(1_000_000_000 / self.clock_speed)
+    }
+
+    fn fetch(&mut self, address: usize) -> Option<Decoded> {
+        if let Some(Some(decoded)) = self.decoded.as_ref().and_then(|decoded| decoded.get(address)) {
+            return Some(*decoded);
+        }
+
+        let opcode = self.opcode_at(address)?;
+        let decoded = (
+            opcode,
+            (
+                opcode.bits(12..16),
+                opcode.bits(8..12),
+                opcode.bits(4..8),
+                opcode.bits(0..4),
+            ),
+        );
+        if let Some(cache) = &mut self.decoded {
+            cache[address] = Some(decoded);
+        }
+
+        Some(decoded)
+    }
+
+    // Addresses past the end of memory wrap around like the 12-bit (or 16-bit
+    // with XO-CHIP) address bus would
+    fn i_address(&self, offset: usize) -> usize {
+        (self.i + offset) & self.address_mask
+    }
+
+    // Nothing can break out of a jump to itself, so once it has run for a while
+    // without the registers changing the program is done
+    fn detect_spin_loop(&mut self, opcode: u16, pc: usize) {
+        let state = (pc, self.v, self.i);
+        if self.spin_state == Some(state) {
+            self.spin_cycles += 1;
+        } else {
+            self.spin_state = Some(state);
+            self.spin_cycles = 1;
+        }
+        if self.spin_cycles < SPIN_LOOP_CYCLES || self.spin_reported {
             return;
         }
-        self.cycles = 0;
 
-        if self.at > 0 {
-            self.at -= 1;
+        self.spin_reported = true;
+        warn!(target: "asm", "{}: program appears to have halted (spin loop)", disassemble(opcode, pc));
+        // Nothing could unpause a headless run, so it halts either way
+        if self.loop_pause && !self.headless {
+            info!(target: "exe", "pausing; press N to step");
+            self.paused = true;
+        } else {
+            self.halted = true;
         }
+    }
 
-        if self.dt > 0 {
-            self.dt -= 1;
+    fn invalidate_decoded(&mut self, address: usize, len: usize) {
+        if let Some(decoded) = &mut self.decoded {
+            // An instruction starting one byte earlier also covers the first byte
+            let start = address.saturating_sub(1);
+            let end = (address + len).min(decoded.len());
+            for entry in &mut decoded[start..end] {
+                *entry = None;
+            }
+        }
+    }
+
+    // Stores wrap around the end of memory, so each byte is marked where it lands
+    fn note_memory_write(&mut self, pc: usize, opcode: u16, len: usize) {
+        let mut reserved: Option<(usize, usize)> = None;
+        for offset in 0..len {
+            let address = self.i_address(offset);
+            self.invalidate_decoded(address, 1);
+            if let Some(written) = &mut self.written {
+                written[address / 64] |= 1 << (address % 64);
+            }
+            if address < PROGRAM_START {
+                reserved = Some(reserved.map_or((address, address), |(start, end)| (start.min(address), end.max(address))));
+            }
+        }
+
+        if let Some((start, end)) = reserved.filter(|_| self.warn_reserved_writes) {
+            warn!(
+                target: "asm",
+                "{:03x}: [{:04x}] writes to reserved memory {:03x}-{:03x}",
+                pc,
+                opcode,
+                start,
+                end,
+            );
+        }
+    }
+
+    // A replay drives the keypad by itself, so live key presses don't reach the program
+    fn keypad(&mut self) -> &mut Keypad {
+        match &mut self.input_replay {
+            Some(replay) => replay.keypad_mut(),
+            None => self.input.keypad_mut(),
+        }
+    }
+
+    fn update_timers(&mut self, elapsed: Duration) {
+        self.timer_accumulator += elapsed;
+        while self.timer_accumulator >= TIMER_RATE {
+            self.timer_accumulator -= TIMER_RATE;
+            self.vblank = self.waiting_for_vblank;
+
+            if self.at > 0 {
+                self.at -= 1;
+            }
+
+            if self.dt > 0 {
+                self.dt -= 1;
+            }
         }
     }
 }
 
+fn load_memory(size: usize, start: usize, font: &[u8], program: &[u8]) -> Result<Vec<u8>, Error> {
+    if program.is_empty() {
+        return Err(Error::InvalidRom("program is empty".into()));
+    }
+
+    let mut memory = vec![0; size];
+    if start < PROGRAM_START || start >= memory.len() {
+        return Err(Error::AddressOutOfBounds {
+            address: start,
+            min: PROGRAM_START,
+            max: memory.len() - 1,
+        });
+    }
+
+    let max = memory.len() - start;
+    if program.len() > max {
+        return Err(Error::RomTooLarge {
+            size: program.len(),
+            max,
+        });
+    }
+
+    memory[FONT_DATA_START..][..font.len()].copy_from_slice(font);
+    memory[LARGE_FONT_DATA_START..][..LARGE_FONT_DATA.len()].copy_from_slice(LARGE_FONT_DATA);
+    memory[start..][..program.len()].copy_from_slice(program);
+
+    Ok(memory)
+}
+
+pub fn read_program(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::with_capacity(0x1000);
+    let size = if path == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut buffer)?
+    } else {
+        File::open(path)?.read_to_end(&mut buffer)?
+    };
+    info!(target: "cli", "read {} bytes from {}", size, path.display());
+
+    Ok(buffer)
+}
+
+fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
+    }
+}
+
+fn flags_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(".chirp8-flags"))
+}
+
+fn read_flags(path: &Path) -> [u8; 8] {
+    let mut flags = [0; 8];
+    if let Ok(bytes) = fs::read(path) {
+        let len = bytes.len().min(flags.len());
+        flags[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    flags
+}
+
 static FONT_DATA: &[u8] = &[
     0xf0, 0x90, 0x90, 0x90, 0xf0, // digit 0
     0x20, 0x60, 0x20, 0x20, 0x70, // digit 1
@@ -409,3 +1513,24 @@ static FONT_DATA: &[u8] = &[
 ];
 const FONT_DATA_START: usize = 0x0;
 const FONT_DIGIT_SIZE: usize = 5;
+
+static LARGE_FONT_DATA: &[u8] = &[
+    0x3c, 0x7e, 0xe7, 0xc3, 0xc3, 0xc3, 0xc3, 0xe7, 0x7e, 0x3c, // digit 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, // digit 1
+    0x3e, 0x7f, 0xc3, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xff, 0xff, // digit 2
+    0x3c, 0x7e, 0xc3, 0x03, 0x0e, 0x0e, 0x03, 0xc3, 0x7e, 0x3c, // digit 3
+    0x06, 0x0e, 0x1e, 0x36, 0x66, 0xc6, 0xff, 0xff, 0x06, 0x06, // digit 4
+    0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfe, 0x03, 0xc3, 0x7e, 0x3c, // digit 5
+    0x3e, 0x7c, 0xe0, 0xc0, 0xfc, 0xfe, 0xc3, 0xc3, 0x7e, 0x3c, // digit 6
+    0xff, 0xff, 0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x60, 0x60, // digit 7
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7e, 0x7e, 0xc3, 0xc3, 0x7e, 0x3c, // digit 8
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7f, 0x3f, 0x03, 0x03, 0x3e, 0x7c, // digit 9
+    0x3c, 0x7e, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, // digit A
+    0xfc, 0xfe, 0xc3, 0xc3, 0xfe, 0xfe, 0xc3, 0xc3, 0xfe, 0xfc, // digit B
+    0x3c, 0x7e, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3, 0x7e, 0x3c, // digit C
+    0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, // digit D
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // digit E
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0, // digit F
+];
+const LARGE_FONT_DATA_START: usize = 0x50;
+const LARGE_FONT_DIGIT_SIZE: usize = 10;
@@ -1,7 +1,14 @@
 use {
-    clap::Clap,
+    crate::{display::Rgb, Error},
+    clap::{ArgSettings, Clap, IntoApp},
     log::LevelFilter,
-    std::{io::Write, path::PathBuf},
+    std::{
+        env,
+        ffi::OsString,
+        fs,
+        io::Write,
+        path::{Path, PathBuf},
+    },
 };
 
 #[derive(Clap, Debug)]
@@ -10,22 +17,311 @@ pub struct Config {
     /// Sets the key mapping to use
     #[clap(short, long, arg_enum, env = "CHIRP_KEYMAP", default_value = "qwerty")]
     pub keymap: Keymap,
+    /// Loads the key mapping from a file instead of using a built-in keymap
+    #[clap(long)]
+    pub keymap_file: Option<PathBuf>,
     /// Sets the rendering size
     #[clap(short, long, arg_enum, default_value = "normal")]
     pub size: Size,
+    /// Sets the rendering scale in window pixels per CHIP-8 pixel, overriding the size
+    #[clap(long)]
+    pub scale: Option<usize>,
+    /// Sets the address (hex) the program is loaded at and starts from
+    #[clap(long, default_value = "200", parse(try_from_str = parse_address))]
+    pub load_address: usize,
+    /// Loads the small hex digit font (16 digits of 5 bytes) from a file
+    #[clap(long)]
+    pub font: Option<PathBuf>,
+    /// Sets the CPU clock speed in instructions per second
+    #[clap(long, default_value = "120")]
+    pub clock_speed: u64,
+    /// Sets a multiplier on the clock speed, like 0.25 for quarter speed
+    #[clap(long, default_value = "1.0")]
+    pub speed: f64,
+    /// Sets how many times faster the emulator runs while Tab is held
+    #[clap(long, default_value = "8")]
+    pub turbo_factor: u32,
+    /// Sets how many seconds of gameplay Backspace can rewind (0 disables rewinding)
+    #[clap(long, default_value = "10")]
+    pub rewind_seconds: u32,
+    /// Sets the frequency of the beeper tone in Hz
+    #[clap(long, default_value = "440")]
+    pub beep_hz: f32,
+    /// Sets the shape of the beeper tone
+    #[clap(long, arg_enum, default_value = "square")]
+    pub waveform: Waveform,
+    /// Sets the beeper volume from 0.0 to 1.0
+    #[clap(long, default_value = "0.25")]
+    pub volume: f32,
+    /// Keeps the beeper on for at least this many milliseconds each time the sound timer is set
+    #[clap(long, default_value = "0")]
+    pub min_beep_ms: u64,
+    /// Sets the quirks to match a platform's behavior
+    #[clap(long, arg_enum, default_value = "modern")]
+    pub profile: Profile,
+    /// Sets which register the shift instructions read from, overriding the profile
+    #[clap(long, arg_enum)]
+    pub shift_quirk: Option<ShiftQuirk>,
+    /// Sets whether register loads and stores advance I, overriding the profile
+    #[clap(long, arg_enum)]
+    pub load_store_quirk: Option<MemoryQuirk>,
+    /// Sets which register `jp v0, addr` adds to the address, overriding the profile
+    #[clap(long, arg_enum)]
+    pub jump_quirk: Option<JumpQuirk>,
+    /// Sets VF when `add i, vx` overflows past the address space
+    #[clap(long)]
+    pub fx1e_overflow_flag: bool,
+    /// Waits for the next 60Hz timer tick before each `drw`, like the COSMAC VIP
+    #[clap(long)]
+    pub display_wait: bool,
+    /// Resets VF after `or`, `and` and `xor`, like the COSMAC VIP
+    #[clap(long)]
+    pub logic_quirk: bool,
+    /// Wraps sprites around the screen edges instead of clipping them, like XO-CHIP
+    #[clap(long)]
+    pub wrap_sprites: bool,
+    /// Skips `sys` calls with a warning instead of halting
+    #[clap(long)]
+    pub ignore_sys: bool,
+    /// Halts when the program keeps jumping to itself, which is how many programs finish
+    #[clap(long)]
+    pub loop_detect: bool,
+    /// Pauses instead of halting when --loop-detect finds a spin loop (implies --loop-detect)
+    #[clap(long)]
+    pub loop_pause: bool,
+    /// Warns when the program writes to the font and stack area below 0x200
+    #[clap(long)]
+    pub warn_reserved_writes: bool,
+    /// Warns when the program executes an instruction it wrote itself
+    #[clap(long)]
+    pub warn_self_modifying: bool,
+    /// Caches decoded instructions by address so hot loops skip decoding
+    #[clap(long)]
+    pub decode_cache: bool,
+    /// Plays a short click whenever a sprite collides with lit pixels
+    #[clap(long)]
+    pub collision_beep: bool,
+    /// Runs without opening an audio device
+    #[clap(long)]
+    pub no_audio: bool,
+    /// Runs without opening a window or audio device
+    #[clap(long)]
+    pub headless: bool,
+    /// Draws the screen in the terminal instead of opening a window
+    #[clap(long)]
+    pub terminal: bool,
+    /// Prints the final screen to stdout on exit
+    #[clap(long)]
+    pub print_screen: bool,
+    /// Prints a hex dump of memory (hex <start>:<len>) on exit
+    #[clap(long, parse(try_from_str = parse_range))]
+    pub dump: Option<(usize, usize)>,
+    /// Runs as fast as possible without a window and reports the instructions per second
+    #[clap(long)]
+    pub benchmark: bool,
+    /// Runs without a window and checks the final screen against this file (as printed by --print-screen)
+    #[clap(long)]
+    pub expect: Option<PathBuf>,
+    /// Prints how many times each kind of instruction ran on exit
+    #[clap(long)]
+    pub coverage: bool,
+    /// Stops after executing this many instructions
+    #[clap(long)]
+    pub max_cycles: Option<u64>,
+    /// Seeds the random number generator for reproducible runs
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// Waits for the monitor's vertical sync when presenting frames
+    #[clap(long)]
+    pub vsync: bool,
+    /// Shows the registers and timers in the corner of the window (toggled with F4)
+    #[clap(long)]
+    pub debug_overlay: bool,
+    /// Reports the instructions and frames per second once a second in the window title or under the terminal screen
+    #[clap(long)]
+    pub show_stats: bool,
+    /// Starts paused, executing one instruction each time N is pressed
+    #[clap(long)]
+    pub step: bool,
+    /// Reads JSON commands from stdin and writes JSON responses to stdout
+    #[clap(long)]
+    pub control: bool,
+    /// Serves the --control commands to one TCP client at this address (like 127.0.0.1:6502) instead of stdin
+    #[clap(long)]
+    pub control_socket: Option<String>,
+    /// Pauses into the debugger when execution reaches this address
+    #[clap(long = "break", number_of_values = 1, parse(try_from_str = parse_address))]
+    pub breakpoints: Vec<usize>,
+    /// Prints a disassembly of the program instead of running it
+    #[clap(long)]
+    pub disassemble: bool,
+    /// Prints the program's size, likely platform and the instructions it uses instead of running it
+    #[clap(long)]
+    pub rom_info: bool,
+    /// Enables XO-CHIP extensions, including 64KB of memory (implied by --profile xochip)
+    #[clap(long)]
+    pub xo_chip: bool,
+    /// Fades pixels out gradually like a phosphor screen to hide flicker
+    #[clap(long)]
+    pub fade: bool,
+    /// Draws a grid between pixels when the window is large enough
+    #[clap(long)]
+    pub grid: bool,
+    /// Sets the color theme of the display
+    #[clap(long, arg_enum, default_value = "default")]
+    pub palette: Palette,
+    /// Overrides the palette's color of lit pixels (#RRGGBB)
+    #[clap(long)]
+    pub fg: Option<Rgb>,
+    /// Overrides the palette's color of unlit pixels (#RRGGBB)
+    #[clap(long)]
+    pub bg: Option<Rgb>,
+    /// Overrides the palette's color of pixels lit only in the second XO-CHIP plane (#RRGGBB)
+    #[clap(long)]
+    pub fg2: Option<Rgb>,
+    /// Overrides the palette's color of pixels lit in both XO-CHIP planes (#RRGGBB)
+    #[clap(long)]
+    pub blend: Option<Rgb>,
+    /// Writes each executed instruction and the registers it changed to this file
+    #[clap(long)]
+    pub trace: Option<PathBuf>,
+    /// Records the presented frames to an animated GIF at this path
+    #[clap(long)]
+    pub record: Option<PathBuf>,
+    /// Records each key press and release with its cycle number to this file
+    #[clap(long)]
+    pub record_input: Option<PathBuf>,
+    /// Feeds the key presses recorded by --record-input back to the program, ignoring the keyboard
+    #[clap(long)]
+    pub replay_input: Option<PathBuf>,
     /// Sets Logging level
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: u8,
-    /// Path to a Chip-8 binary
-    pub program: PathBuf,
+    /// Lists the programs in this directory to choose from when no program is given
+    #[clap(long, default_value = ".")]
+    pub rom_dir: PathBuf,
+    /// Path to a Chip-8 binary, or - to read it from stdin (chosen from --rom-dir when left out)
+    pub program: Option<PathBuf>,
 }
 
 #[derive(Clap, Debug)]
 pub enum Keymap {
+    Azerty,
     Colemak,
     Qwerty,
 }
 
+impl Config {
+    // Options from the config file go in front of the command line's, skipping
+    // any the command line sets itself since clap rejects repeated options
+    pub fn load() -> Result<Config, Error> {
+        let args = env::args_os().collect::<Vec<_>>();
+        match config_path() {
+            Some(path) if path.is_file() => Config::load_from(&path, args),
+            _ => Ok(Config::parse_from(args)),
+        }
+    }
+
+    pub fn load_from(path: &Path, args: Vec<OsString>) -> Result<Config, Error> {
+        let app = Config::into_app();
+        let matches = app.clone().get_matches_from(&args);
+        let mut defaults = Vec::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: &str| Error::InvalidOption(format!("{}:{}: {}", path.display(), number + 1, message));
+            let (name, value) = line.split_once('=').ok_or_else(|| error("expected `option = value`"))?;
+            let name = name.trim();
+            let value = value.trim().trim_matches('"');
+
+            let arg = app
+                .get_arguments()
+                .iter()
+                .find(|arg| arg.get_long() == Some(name))
+                .ok_or_else(|| error(&format!("unknown option {}", name)))?;
+            if matches.occurrences_of(arg.get_name()) > 0 {
+                continue;
+            }
+
+            if arg.is_set(ArgSettings::TakesValue) {
+                defaults.push(OsString::from(format!("--{}", name)));
+                defaults.push(OsString::from(value));
+            } else {
+                match value {
+                    "true" => defaults.push(OsString::from(format!("--{}", name))),
+                    "false" => {}
+                    _ => return Err(error(&format!("{} takes true or false", name))),
+                }
+            }
+        }
+
+        Ok(Config::parse_from(args.iter().take(1).chain(&defaults).chain(args.iter().skip(1))))
+    }
+
+    pub fn colors(&self) -> [Rgb; 4] {
+        let [bg, fg, fg2, blend] = self.palette.colors();
+        [self.bg.unwrap_or(bg), self.fg.unwrap_or(fg), self.fg2.unwrap_or(fg2), self.blend.unwrap_or(blend)]
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale.unwrap_or_else(|| self.size.scale())
+    }
+
+    pub fn xo_chip(&self) -> bool {
+        self.xo_chip || self.profile == Profile::Xochip
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        let quirks = self.profile.quirks();
+        Quirks {
+            shift: self.shift_quirk.unwrap_or(quirks.shift),
+            load_store: self.load_store_quirk.unwrap_or(quirks.load_store),
+            jump: self.jump_quirk.unwrap_or(quirks.jump),
+            fx1e_overflow_flag: self.fx1e_overflow_flag || quirks.fx1e_overflow_flag,
+            display_wait: self.display_wait || quirks.display_wait,
+            logic: self.logic_quirk || quirks.logic,
+            wrap: self.wrap_sprites || quirks.wrap,
+        }
+    }
+}
+
+#[derive(Clap, Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    Default,
+    Gameboy,
+    Amber,
+    C64,
+}
+
+impl Palette {
+    pub fn colors(self) -> [Rgb; 4] {
+        match self {
+            Palette::Default => [Rgb::BLACK, Rgb::WHITE, Rgb(0xaa, 0xaa, 0xaa), Rgb(0x55, 0x55, 0x55)],
+            Palette::Gameboy => [
+                Rgb(0x9b, 0xbc, 0x0f),
+                Rgb(0x0f, 0x38, 0x0f),
+                Rgb(0x8b, 0xac, 0x0f),
+                Rgb(0x30, 0x62, 0x30),
+            ],
+            Palette::Amber => [
+                Rgb(0x1a, 0x12, 0x00),
+                Rgb(0xff, 0xb0, 0x00),
+                Rgb(0x80, 0x58, 0x00),
+                Rgb(0xff, 0xd8, 0x80),
+            ],
+            Palette::C64 => [
+                Rgb(0x40, 0x31, 0x8d),
+                Rgb(0x78, 0x69, 0xc4),
+                Rgb(0x67, 0xb6, 0xbd),
+                Rgb(0xff, 0xff, 0xff),
+            ],
+        }
+    }
+}
+
 #[derive(Clap, Debug)]
 pub enum Size {
     Small,
@@ -33,6 +329,140 @@ pub enum Size {
     Large,
 }
 
+impl Size {
+    pub fn scale(&self) -> usize {
+        match self {
+            Size::Small => 4,
+            Size::Normal => 8,
+            Size::Large => 16,
+        }
+    }
+}
+
+#[derive(Clap, Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    Sawtooth,
+}
+
+#[derive(Clap, Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    Cosmac,
+    Schip,
+    Xochip,
+    Modern,
+}
+
+impl Profile {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Profile::Cosmac => Quirks {
+                shift: ShiftQuirk::Vy,
+                load_store: MemoryQuirk::Increment,
+                jump: JumpQuirk::V0,
+                fx1e_overflow_flag: false,
+                display_wait: true,
+                logic: true,
+                wrap: false,
+            },
+            Profile::Schip => Quirks {
+                shift: ShiftQuirk::Vx,
+                load_store: MemoryQuirk::Keep,
+                jump: JumpQuirk::Vx,
+                fx1e_overflow_flag: false,
+                display_wait: false,
+                logic: false,
+                wrap: false,
+            },
+            Profile::Xochip => Quirks {
+                shift: ShiftQuirk::Vy,
+                load_store: MemoryQuirk::Increment,
+                jump: JumpQuirk::V0,
+                fx1e_overflow_flag: false,
+                display_wait: false,
+                logic: false,
+                wrap: true,
+            },
+            Profile::Modern => Quirks {
+                shift: ShiftQuirk::Vx,
+                load_store: MemoryQuirk::Increment,
+                jump: JumpQuirk::V0,
+                fx1e_overflow_flag: false,
+                display_wait: false,
+                logic: false,
+                wrap: false,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    pub shift: ShiftQuirk,
+    pub load_store: MemoryQuirk,
+    pub jump: JumpQuirk,
+    pub fx1e_overflow_flag: bool,
+    pub display_wait: bool,
+    pub logic: bool,
+    pub wrap: bool,
+}
+
+#[derive(Clap, Clone, Copy, Debug, PartialEq)]
+pub enum ShiftQuirk {
+    Vx,
+    Vy,
+}
+
+#[derive(Clap, Clone, Copy, Debug, PartialEq)]
+pub enum JumpQuirk {
+    V0,
+    Vx,
+}
+
+#[derive(Clap, Clone, Copy, Debug, PartialEq)]
+pub enum MemoryQuirk {
+    Increment,
+    Keep,
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(&env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(dir.join("chirp8").join("config.toml"))
+}
+
+// A # starts a comment at the start of a line or after a space, but not inside
+// quotes, so colors like "#00ff00" survive
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted && previous.is_whitespace() => return &line[..index],
+            _ => {}
+        }
+        previous = c;
+    }
+
+    line
+}
+
+pub fn parse_range(s: &str) -> Result<(usize, usize), String> {
+    let (start, len) = s.split_once(':').ok_or_else(|| format!("{}: expected <start>:<len>", s))?;
+    Ok((parse_address(start)?, parse_address(len)?))
+}
+
+pub fn parse_address(s: &str) -> Result<usize, String> {
+    let digits = s.trim_start_matches("0x");
+    usize::from_str_radix(digits, 16).map_err(|e| format!("{}: {}", s, e))
+}
+
 pub fn configure_logging(level: u8) {
     env_logger::builder()
         .format(|f, record| writeln!(f, "{:>5}: {}", record.level(), record.args()))
@@ -0,0 +1,209 @@
+use {
+    crate::{debugger::Resume, Chip8, Error},
+    log::*,
+    serde::{Deserialize, Serialize},
+    std::{
+        io::{self, BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::mpsc::{self, Receiver, TryRecvError},
+        thread,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    Step {
+        #[serde(default = "one")]
+        count: u64,
+    },
+    Continue,
+    Pause,
+    ReadMemory {
+        address: usize,
+        length: usize,
+    },
+    ReadRegisters,
+    SetBreakpoint {
+        address: usize,
+    },
+    InjectKey {
+        key: u8,
+        pressed: bool,
+    },
+    Quit,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "response", rename_all = "kebab-case")]
+pub enum Response {
+    Ok,
+    Registers {
+        v: [u8; 16],
+        i: usize,
+        pc: usize,
+        sp: usize,
+        dt: u8,
+        st: u8,
+        cycles: u64,
+        halted: bool,
+    },
+    Memory {
+        address: usize,
+        bytes: Vec<u8>,
+    },
+    Break {
+        pc: usize,
+    },
+    Error {
+        message: String,
+    },
+}
+
+pub struct Control {
+    commands: Receiver<String>,
+    responses: Box<dyn Write + Send>,
+}
+
+impl Control {
+    pub fn new() -> Control {
+        info!(target: "exe", "reading control commands from stdin");
+        Control {
+            commands: read_commands(BufReader::new(io::stdin())),
+            responses: Box::new(io::stdout()),
+        }
+    }
+
+    // Serves one client, which has to connect before the program starts
+    pub fn listen(address: &str) -> Result<Control, Error> {
+        let listener = TcpListener::bind(address)?;
+        info!(target: "exe", "waiting for a control connection on {}", listener.local_addr()?);
+        let (stream, peer) = listener.accept()?;
+        info!(target: "exe", "reading control commands from {}", peer);
+
+        Ok(Control {
+            commands: read_commands(BufReader::new(stream.try_clone()?)),
+            responses: Box::new(stream),
+        })
+    }
+
+    pub fn process(&mut self, c8: &mut Chip8) -> Result<Resume, Error> {
+        loop {
+            let line = match self.commands.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => return Ok(Resume::Continue),
+                Err(TryRecvError::Disconnected) => return Ok(Resume::Quit),
+            };
+
+            trace!(target: "exe", "control command: {}", line);
+            let response = match serde_json::from_str(&line) {
+                Ok(Command::Quit) => {
+                    self.respond(&Response::Ok)?;
+                    return Ok(Resume::Quit);
+                }
+                Ok(command) => execute(c8, command)?,
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            };
+            self.respond(&response)?;
+        }
+    }
+
+    pub fn respond(&mut self, response: &Response) -> Result<(), Error> {
+        let json = serde_json::to_string(response).map_err(io::Error::other)?;
+        writeln!(self.responses, "{}", json)?;
+        self.responses.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Default for Control {
+    fn default() -> Control {
+        Control::new()
+    }
+}
+
+// Commands are read on their own thread so the run loop can poll for them
+fn read_commands(reader: impl BufRead + Send + 'static) -> Receiver<String> {
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            match line {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    warn!(target: "exe", "couldn't read a control command: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    commands
+}
+
+fn execute(c8: &mut Chip8, command: Command) -> Result<Response, Error> {
+    Ok(match command {
+        Command::Step { count } => {
+            for _ in 0..count {
+                c8.cycle()?;
+            }
+            registers(c8)
+        }
+        Command::Continue => {
+            c8.set_paused(false);
+            Response::Ok
+        }
+        Command::Pause => {
+            c8.set_paused(true);
+            Response::Ok
+        }
+        Command::ReadMemory { address, length } => {
+            let end = address.saturating_add(length).min(c8.memory().len());
+            Response::Memory {
+                address,
+                bytes: c8.memory()[address.min(end)..end].to_vec(),
+            }
+        }
+        Command::ReadRegisters => registers(c8),
+        Command::SetBreakpoint { address } => {
+            c8.add_breakpoint(address);
+            Response::Ok
+        }
+        Command::InjectKey { key, .. } if key >= 16 => Response::Error {
+            message: format!("{:x} is not a CHIP-8 key", key),
+        },
+        Command::InjectKey { key, pressed: true } => {
+            c8.keypad_mut().key_down(key);
+            Response::Ok
+        }
+        Command::InjectKey { key, pressed: false } => {
+            c8.keypad_mut().key_up(key);
+            Response::Ok
+        }
+        Command::Quit => Response::Ok,
+    })
+}
+
+fn registers(c8: &Chip8) -> Response {
+    Response::Registers {
+        v: *c8.registers(),
+        i: c8.i(),
+        pc: c8.pc(),
+        sp: c8.sp(),
+        dt: c8.delay_timer(),
+        st: c8.sound_timer(),
+        cycles: c8.cycles(),
+        halted: c8.is_halted(),
+    }
+}
+
+fn one() -> u64 {
+    1
+}
@@ -0,0 +1,81 @@
+use {
+    crate::{cli::parse_address, disassembler::disassemble, Chip8, Error},
+    std::io::{self, BufRead, Write},
+};
+
+pub enum Resume {
+    Continue,
+    Quit,
+}
+
+pub fn prompt(c8: &mut Chip8) -> Result<Resume, Error> {
+    println!("break at {:03x}", c8.pc());
+    println!("{}", c8.registers_string());
+
+    let stdin = io::stdin();
+    loop {
+        print!("(chirp8) ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(Resume::Quit);
+        }
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("c") | Some("continue") => return Ok(Resume::Continue),
+            Some("q") | Some("quit") => return Ok(Resume::Quit),
+            Some("s") | Some("step") => step(c8)?,
+            Some("n") | Some("next") => match c8.opcode_at(c8.pc()) {
+                Some(opcode) if opcode >> 12 == 0x2 => {
+                    c8.break_on_return();
+                    return Ok(Resume::Continue);
+                }
+                _ => step(c8)?,
+            },
+            Some("r") | Some("registers") => println!("{}", c8.registers_string()),
+            Some("m") | Some("memory") => {
+                let start = match words.next().map(parse_address) {
+                    Some(Ok(start)) => start,
+                    Some(Err(e)) => {
+                        println!("invalid address: {}", e);
+                        continue;
+                    }
+                    None => c8.i(),
+                };
+                let len = match words.next().map(parse_address) {
+                    Some(Ok(len)) => len,
+                    Some(Err(e)) => {
+                        println!("invalid length: {}", e);
+                        continue;
+                    }
+                    None => 0x10,
+                };
+
+                print!("{}", c8.dump_memory(start, len));
+            }
+            Some("h") | Some("help") => {
+                println!("c, continue         resume execution");
+                println!("s, step             execute one instruction");
+                println!("n, next             execute one instruction, running a `call` until it returns");
+                println!("r, registers        print the registers");
+                println!("m, memory [a] [n]   print n bytes of memory from a (defaults to i, 10)");
+                println!("q, quit             stop the emulator");
+            }
+            Some(command) => println!("unknown command `{}`; try `help`", command),
+            None => {}
+        }
+    }
+}
+
+fn step(c8: &mut Chip8) -> Result<(), Error> {
+    let pc = c8.pc();
+    c8.cycle()?;
+    if let Some(opcode) = c8.opcode_at(pc) {
+        println!("{}", disassemble(opcode, pc));
+    }
+    println!("{}", c8.registers_string());
+
+    Ok(())
+}
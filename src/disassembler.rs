@@ -0,0 +1,129 @@
+use {
+    quark::BitIndex,
+    std::fmt::{self, Display, Formatter},
+};
+
+pub fn disassemble(opcode: u16, address: usize) -> String {
+    format!("{:03x}: [{:04x}] {}", address, opcode, instruction(opcode))
+}
+
+pub struct Instruction {
+    pub mnemonic: &'static str,
+    x: u16,
+    y: u16,
+    n: u16,
+    kk: u16,
+    nnn: u16,
+}
+
+// Decodes the opcode into its mnemonic, with the operands left generic (like
+// `se vx, kk`) so instructions of the same kind can be grouped
+pub fn decode(opcode: u16) -> Instruction {
+    let x = opcode.bits(8..12);
+    let y = opcode.bits(4..8);
+    let mut n = opcode.bits(0..4);
+
+    let mnemonic = match (opcode.bits(12..16), x, y, n) {
+        (0x0, 0x0, 0xe, 0x0) => "cls",
+        (0x0, 0x0, 0xe, 0xe) => "ret",
+        (0x0, 0x0, 0xc, _) => "scd n",
+        (0x0, 0x0, 0xf, 0xb) => "scr",
+        (0x0, 0x0, 0xf, 0xc) => "scl",
+        (0x0, 0x0, 0xf, 0xd) => "exit",
+        (0x0, 0x0, 0xf, 0xe) => "low",
+        (0x0, 0x0, 0xf, 0xf) => "high",
+        (0x0, ..) => "sys nnn",
+        (0x1, ..) => "jp nnn",
+        (0x2, ..) => "call nnn",
+        (0x3, ..) => "se vx, kk",
+        (0x4, ..) => "sne vx, kk",
+        (0x5, _, _, 0x0) => "se vx, vy",
+        (0x5, _, _, 0x2) => "ld [i], vx-vy",
+        (0x5, _, _, 0x3) => "ld vx-vy, [i]",
+        (0x6, ..) => "ld vx, kk",
+        (0x7, ..) => "add vx, kk",
+        (0x8, _, _, 0x0) => "ld vx, vy",
+        (0x8, _, _, 0x1) => "or vx, vy",
+        (0x8, _, _, 0x2) => "and vx, vy",
+        (0x8, _, _, 0x3) => "xor vx, vy",
+        (0x8, _, _, 0x4) => "add vx, vy",
+        (0x8, _, _, 0x5) => "sub vx, vy",
+        (0x8, _, _, 0x6) => "shr vx, vy",
+        (0x8, _, _, 0x7) => "subn vx, vy",
+        (0x8, _, _, 0xe) => "shl vx, vy",
+        (0x9, _, _, 0x0) => "sne vx, vy",
+        (0xa, ..) => "ld i, nnn",
+        (0xb, ..) => "jp v0, nnn",
+        (0xc, ..) => "rnd vx, kk",
+        (0xd, ..) => "drw vx, vy, n",
+        (0xe, _, 0x9, 0xe) => "skp vx",
+        (0xe, _, 0xa, 0x1) => "sknp vx",
+        (0xf, 0x0, 0x0, 0x0) => "ld i, long",
+        (0xf, 0x0, 0x0, 0x2) => "audio",
+        (0xf, _, 0x0, 0x1) => {
+            // The plane mask sits where the register usually goes
+            n = x;
+            "plane n"
+        }
+        (0xf, _, 0x0, 0x7) => "ld vx, dt",
+        (0xf, _, 0x0, 0xa) => "ld vx, k",
+        (0xf, _, 0x1, 0x5) => "ld dt, vx",
+        (0xf, _, 0x1, 0x8) => "ld st, vx",
+        (0xf, _, 0x1, 0xe) => "add i, vx",
+        (0xf, _, 0x2, 0x9) => "ld f, vx",
+        (0xf, _, 0x3, 0x0) => "ld hf, vx",
+        (0xf, _, 0x3, 0xa) => "pitch vx",
+        (0xf, _, 0x3, 0x3) => "ld b, vx",
+        (0xf, _, 0x5, 0x5) => "ld [i], vx",
+        (0xf, _, 0x6, 0x5) => "ld vx, [i]",
+        (0xf, _, 0x7, 0x5) => "ld r, vx",
+        (0xf, _, 0x8, 0x5) => "ld vx, r",
+        _ => "unknown instruction",
+    };
+
+    Instruction {
+        mnemonic,
+        x,
+        y,
+        n,
+        kk: opcode.bits(0..8),
+        nnn: opcode.bits(0..12),
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Operands are whole words of the mnemonic, and everything else is copied as is
+        let mut rest = self.mnemonic;
+        while !rest.is_empty() {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len()).max(1);
+            let (word, tail) = rest.split_at(end);
+            match word {
+                "vx" => write!(f, "v{:1x}", self.x)?,
+                "vy" => write!(f, "v{:1x}", self.y)?,
+                "n" => write!(f, "{:1x}", self.n)?,
+                "kk" => write!(f, "{:02x}", self.kk)?,
+                "nnn" => write!(f, "{:03x}", self.nnn)?,
+                _ => f.write_str(word)?,
+            }
+            rest = tail;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn instruction(opcode: u16) -> String {
+    decode(opcode).to_string()
+}
+
+pub fn disassemble_program(program: &[u8], start: usize) -> impl Iterator<Item = String> + '_ {
+    program.chunks(2).enumerate().map(move |(index, bytes)| {
+        let address = start + index * 2;
+        match *bytes {
+            [high, low] => disassemble(u16::from_be_bytes([high, low]), address),
+            [byte] => format!("{:03x}: [{:02x}  ] db {:02x}", address, byte, byte),
+            _ => unreachable!(),
+        }
+    })
+}
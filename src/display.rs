@@ -1,66 +1,176 @@
 use {
-    crate::{cli::{Config, Size}, Error},
+    crate::Error,
     log::*,
-    sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window, Sdl},
-    std::fmt::{self, Formatter},
+    std::{fmt::{self, Formatter}, str::FromStr},
 };
 
-const SCREEN_WIDTH_PIXELS: usize = 64;
-const SCREEN_HEIGHT_PIXELS: usize = 32;
+pub const SCREEN_WIDTH_PIXELS: usize = 64;
+pub const SCREEN_HEIGHT_PIXELS: usize = 32;
 
-pub struct Display {
-    w: usize,
-    h: usize,
-    scale: usize,
-    pixels: Vec<u8>,
-    canvas: Canvas<Window>,
-    dirty: bool,
+pub trait Display {
+    fn framebuffer(&self) -> &Framebuffer;
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer;
+    fn present(&mut self) -> Result<(), Error>;
+
+    fn pixels(&self) -> Vec<u8> {
+        self.framebuffer().pixels()
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        (self.framebuffer().w, self.framebuffer().h)
+    }
+
+    fn is_pixel_set(&self, x: usize, y: usize) -> bool {
+        let (w, h) = self.dimensions();
+        x < w && y < h && self.framebuffer().pixel(x, y) != 0
+    }
+
+    fn needs_presenting(&self) -> bool {
+        self.framebuffer().dirty_rect.is_some()
+    }
+
+    fn clear_screen(&mut self) {
+        self.framebuffer_mut().clear_screen();
+    }
+
+    fn draw_sprite(&mut self, sprite: &[u8], x: u8, y: u8, wrap: bool) -> bool {
+        self.framebuffer_mut().draw_sprite(sprite, x, y, wrap)
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        self.framebuffer_mut().scroll_down(rows);
+    }
+
+    fn scroll_left(&mut self, columns: usize) {
+        self.framebuffer_mut().scroll_left(columns);
+    }
+
+    fn scroll_right(&mut self, columns: usize) {
+        self.framebuffer_mut().scroll_right(columns);
+    }
+
+    fn select_planes(&mut self, planes: u8) {
+        self.framebuffer_mut().select_planes(planes);
+    }
+
+    fn request_screenshot(&mut self) {}
+
+    fn show_stats(&mut self, _stats: &str) {}
+
+    fn show_overlay(&mut self, _text: Option<&str>) {}
+}
+
+pub struct Framebuffer {
+    pub(crate) w: usize,
+    pub(crate) h: usize,
+    // One bit per pixel for each plane, with the leftmost column in the high bit
+    rows: [[u64; SCREEN_HEIGHT_PIXELS]; 2],
+    pub(crate) dirty_rect: Option<Rect>,
+    pub(crate) planes: u8,
 }
 
-impl Display {
-    pub fn new(sdl: &Sdl, config: &Config) -> Result<Display, Error> {
-        let scale = match config.size {
-            Size::Small => 4,
-            Size::Normal => 8,
-            Size::Large => 16,
-        };
-
-        let width = SCREEN_WIDTH_PIXELS * scale;
-        let height = SCREEN_HEIGHT_PIXELS * scale;
-
-        info!(target: "sdl", "creating window at {}x scale ({}x{} pixels)", scale, width, height);
-        let video = sdl.video()?;
-        let window = video
-            .window("CHIP-8", width as u32, height as u32)
-            .position_centered()
-            .build()?;
-        let canvas = window.into_canvas().build()?;
-
-        Ok(Display {
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer {
             w: SCREEN_WIDTH_PIXELS,
             h: SCREEN_HEIGHT_PIXELS,
-            scale,
-            pixels: vec![0; SCREEN_WIDTH_PIXELS * SCREEN_HEIGHT_PIXELS],
-            canvas,
-            dirty: true,
-        })
+            rows: [[0; SCREEN_HEIGHT_PIXELS]; 2],
+            dirty_rect: Some(Rect::new(0, 0, SCREEN_WIDTH_PIXELS, SCREEN_HEIGHT_PIXELS)),
+            planes: 1,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let bit = 63 - x;
+        (((self.rows[0][y] >> bit) & 1) | (((self.rows[1][y] >> bit) & 1) << 1)) as u8
+    }
+
+    pub fn pixels(&self) -> Vec<u8> {
+        (0..self.h)
+            .flat_map(|y| (0..self.w).map(move |x| self.pixel(x, y)))
+            .collect()
+    }
+
+    pub fn set_pixels(&mut self, pixels: &[u8]) {
+        self.rows = [[0; SCREEN_HEIGHT_PIXELS]; 2];
+        for (index, &pixel) in pixels.iter().enumerate().take(self.w * self.h) {
+            let (x, y) = (index % self.w, index / self.w);
+            for (plane, rows) in self.rows.iter_mut().enumerate() {
+                rows[y] |= (((pixel >> plane) & 1) as u64) << (63 - x);
+            }
+        }
+
+        self.invalidate();
+    }
+
+    pub fn matches(&self, expected: &str) -> bool {
+        let actual = self.to_string();
+        actual.trim_end().lines().map(str::trim_end).eq(expected.trim_end().lines().map(str::trim_end))
+    }
+
+    fn selected_planes(&self) -> impl Iterator<Item = usize> {
+        let selected = self.planes;
+        (0..2).filter(move |plane| selected & (1 << plane) != 0)
     }
 
-    pub fn needs_presenting(&self) -> bool {
-        self.dirty
+    pub fn invalidate(&mut self) {
+        self.mark_dirty(Rect::new(0, 0, self.w, self.h));
     }
 
-    pub fn clear_screen(&mut self) -> Result<(), Error> {
+    fn mark_dirty(&mut self, rect: Rect) {
+        self.dirty_rect = Some(match self.dirty_rect {
+            Some(dirty_rect) => dirty_rect.union(rect),
+            None => rect,
+        });
+    }
+
+    pub fn select_planes(&mut self, planes: u8) {
+        debug!(target: "dsp", "selecting planes {:02b}", planes);
+        self.planes = planes & 0b11;
+    }
+
+    pub fn clear_screen(&mut self) {
         debug!(target: "dsp", "clearing screen backing buffer");
-        for pixel in self.pixels.iter_mut() {
-            *pixel = 0;
+        for plane in self.selected_planes() {
+            self.rows[plane] = [0; SCREEN_HEIGHT_PIXELS];
         }
 
-        self.dirty = true;
-        Ok(())
+        self.invalidate();
     }
 
-    pub fn draw_sprite(&mut self, sprite: &[u8], x: u8, y: u8) -> Result<bool, Error> {
+    pub fn scroll_down(&mut self, rows: usize) {
+        debug!(target: "dsp", "scrolling backing buffer down {} rows", rows);
+        self.scroll(0, rows as isize);
+    }
+
+    pub fn scroll_left(&mut self, columns: usize) {
+        debug!(target: "dsp", "scrolling backing buffer left {} columns", columns);
+        self.scroll(-(columns as isize), 0);
+    }
+
+    pub fn scroll_right(&mut self, columns: usize) {
+        debug!(target: "dsp", "scrolling backing buffer right {} columns", columns);
+        self.scroll(columns as isize, 0);
+    }
+
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        for plane in self.selected_planes() {
+            let source = self.rows[plane];
+            for (y, row) in self.rows[plane].iter_mut().enumerate() {
+                let sy = y as isize - dy;
+                let value = if (0..source.len() as isize).contains(&sy) { source[sy as usize] } else { 0 };
+                *row = if dx >= 0 {
+                    value.checked_shr(dx as u32).unwrap_or(0)
+                } else {
+                    value.checked_shl(dx.unsigned_abs() as u32).unwrap_or(0)
+                };
+            }
+        }
+
+        self.invalidate();
+    }
+
+    pub fn draw_sprite(&mut self, sprite: &[u8], x: u8, y: u8, wrap: bool) -> bool {
         debug!(target: "dsp", "drawing sprite to backing buffer");
         if log_enabled!(target: "dsp", Level::Trace) {
             let mut chunks = sprite.chunks_exact(2);
@@ -92,75 +202,128 @@ impl Display {
             }
         }
 
-        let x = x as usize;
-        let y = y as usize;
+        let x = x as usize % self.w;
+        let y = y as usize % self.h;
         let mut toggled_off = false;
 
-        for dy in 0..sprite.len() {
-            let mut byte = sprite[dy].reverse_bits();
-            for dx in 0..8 {
-                if byte & 1 != 0 {
-                    let index = (y + dy) * self.w + (x + dx);
-                    match self.pixels[index] {
-                        0 => self.pixels[index] = 1,
-                        1 => {
-                            self.pixels[index] = 0;
-                            toggled_off = true;
-                        }
-                        _ => unsafe { std::hint::unreachable_unchecked() },
-                    }
+        let plane_count = self.planes.count_ones() as usize;
+        if plane_count == 0 {
+            return false;
+        }
+
+        let height = sprite.len() / plane_count;
+        let planes: Vec<_> = self.selected_planes().collect();
+        for (plane, rows) in planes.into_iter().zip(sprite.chunks(height.max(1))) {
+            for (dy, &byte) in rows.iter().enumerate() {
+                if y + dy >= self.h && !wrap {
+                    break;
                 }
-                byte >>= 1;
+
+                // Bits shifted past the right edge fall off, clipping the sprite,
+                // unless they wrap around to the left edge
+                let bits = if wrap {
+                    ((byte as u64) << 56).rotate_right(x as u32)
+                } else {
+                    ((byte as u64) << 56) >> x
+                };
+                let row = &mut self.rows[plane][(y + dy) % self.h];
+                if *row & bits != 0 {
+                    toggled_off = true;
+                }
+                *row ^= bits;
             }
         }
 
-        self.dirty = true;
-        Ok(toggled_off)
+        let rows = height.min(self.h - y);
+        if wrap && (x + 8 > self.w || y + height > self.h) {
+            self.invalidate();
+        } else if rows > 0 {
+            self.mark_dirty(Rect::new(x, y, 8.min(self.w - x), rows));
+        }
+
+        toggled_off
     }
+}
 
-    pub fn present(&mut self) -> Result<(), String> {
-        debug!(target: "sdl", "updating canvas");
-        let scale = self.scale as f32;
-        self.canvas.set_scale(scale, scale)?;
+impl Display for Framebuffer {
+    fn framebuffer(&self) -> &Framebuffer {
+        self
+    }
 
-        trace!(target: "sdl", "clearing canvas");
-        self.canvas.set_draw_color(Color::BLACK);
-        self.canvas.clear();
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        self
+    }
 
-        self.canvas.set_draw_color(Color::WHITE);
-        for y in 0..self.h {
-            for x in 0..self.w {
-                let index = y * self.w + x;
-                if self.pixels[index] != 0 {
-                    trace!(target: "sdl", "drawing pixel ({}, {})", x, y);
-                    let pixel = Rect::new(x as i32, y as i32, 1, 1);
-                    self.canvas.fill_rect(pixel)?;
-                }
-            }
+    fn present(&mut self) -> Result<(), Error> {
+        self.dirty_rect = None;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0x00, 0x00, 0x00);
+    pub const WHITE: Rgb = Rgb(0xff, 0xff, 0xff);
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rgb, String> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if digits.len() != 6 {
+            return Err(format!("{}: expected a color like #RRGGBB", s));
         }
 
-        debug!(target: "sdl", "presenting canvas");
-        self.canvas.present();
+        let value = u32::from_str_radix(digits, 16).map_err(|e| format!("{}: {}", s, e))?;
+        let [_, r, g, b] = value.to_be_bytes();
+        Ok(Rgb(r, g, b))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
 
-        self.dirty = false;
-        Ok(())
+impl Rect {
+    pub fn new(x: usize, y: usize, w: usize, h: usize) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    pub fn union(self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.w).max(other.x + other.w);
+        let bottom = (self.y + self.h).max(other.y + other.h);
+
+        Rect::new(x, y, right - x, bottom - y)
     }
 }
 
-impl fmt::Display for Display {
+impl Default for Framebuffer {
+    fn default() -> Framebuffer {
+        Framebuffer::new()
+    }
+}
+
+impl fmt::Display for Framebuffer {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        for rows in self.pixels.chunks_exact(self.w * 2) {
-            let first = &rows[..self.w];
-            let second = &rows[self.w..];
-            for pair in first.iter().zip(second.iter()) {
-                match pair {
+        for y in (0..self.h).step_by(2) {
+            for x in 0..self.w {
+                match (self.pixel(x, y), self.pixel(x, y + 1)) {
                     (0, 0) => write!(f, " ")?,
                     (0, _) => write!(f, "▄")?,
                     (_, 0) => write!(f, "▀")?,
                     (_, _) => write!(f, "█")?,
                 }
             }
-            println!();
+            writeln!(f)?;
         }
 
         Ok(())
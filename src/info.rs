@@ -0,0 +1,142 @@
+use {
+    crate::disassembler,
+    quark::BitIndex,
+    std::{
+        collections::BTreeMap,
+        fmt::{self, Display, Formatter},
+    },
+};
+
+const LOAD_ADDRESSES: [usize; 2] = [0x200, 0x600];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    Chip8,
+    Schip,
+    XoChip,
+}
+
+pub struct RomInfo {
+    pub size: usize,
+    pub load_address: usize,
+    pub platform: Platform,
+    pub uses_random: bool,
+    pub instructions: BTreeMap<String, usize>,
+}
+
+impl RomInfo {
+    pub fn scan(program: &[u8], load_address: usize) -> RomInfo {
+        let opcodes = trace(program, load_address);
+
+        let mut instructions = BTreeMap::new();
+        for &opcode in &opcodes {
+            let instruction = disassembler::instruction(opcode);
+            let class = instruction.split(' ').next().unwrap_or_default().to_string();
+            *instructions.entry(class).or_insert(0) += 1;
+        }
+
+        let platform = if opcodes.iter().any(|&opcode| is_xo_chip(opcode)) {
+            Platform::XoChip
+        } else if opcodes.iter().any(|&opcode| is_schip(opcode)) {
+            Platform::Schip
+        } else {
+            Platform::Chip8
+        };
+
+        // Jumps, calls and loads of I mostly point into the program, which
+        // shows where it was written to be loaded
+        let targets = program
+            .chunks_exact(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .filter(|&opcode| matches!(opcode.bits(12..16), 0x1 | 0x2 | 0xa))
+            .map(|opcode| opcode.bits(0..12) as usize)
+            .collect::<Vec<_>>();
+        let suggested_address = LOAD_ADDRESSES
+            .iter()
+            .copied()
+            .rev()
+            .max_by_key(|&start| targets.iter().filter(|&&target| (start..start + program.len()).contains(&target)).count())
+            .unwrap_or(LOAD_ADDRESSES[0]);
+
+        RomInfo {
+            size: program.len(),
+            load_address: suggested_address,
+            platform,
+            uses_random: opcodes.iter().any(|&opcode| opcode.bits(12..16) == 0xc),
+            instructions,
+        }
+    }
+}
+
+impl Display for RomInfo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "size: {} bytes", self.size)?;
+        writeln!(f, "load address: {:03x}", self.load_address)?;
+        writeln!(
+            f,
+            "platform: {}",
+            match self.platform {
+                Platform::Chip8 => "CHIP-8",
+                Platform::Schip => "SCHIP (try --profile schip)",
+                Platform::XoChip => "XO-CHIP (try --profile xochip)",
+            }
+        )?;
+        writeln!(f, "random: {}", if self.uses_random { "yes" } else { "no" })?;
+        write!(f, "instructions:")?;
+        for (class, count) in &self.instructions {
+            write!(f, " {} ({})", class, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Follows the control flow from the start of the program so sprites and other
+// data between the code don't get counted as instructions
+fn trace(program: &[u8], load_address: usize) -> Vec<u16> {
+    let end = load_address + program.len();
+    let mut visited = vec![false; program.len()];
+    let mut opcodes = Vec::new();
+    let mut pending = vec![load_address];
+
+    while let Some(address) = pending.pop() {
+        if address < load_address || address + 1 >= end || visited[address - load_address] {
+            continue;
+        }
+        visited[address - load_address] = true;
+
+        let offset = address - load_address;
+        let opcode = u16::from_be_bytes([program[offset], program[offset + 1]]);
+        opcodes.push(opcode);
+
+        let nnn = opcode.bits(0..12) as usize;
+        match (opcode.bits(12..16), opcode.bits(8..12), opcode.bits(4..8), opcode.bits(0..4)) {
+            (0x0, 0x0, 0xe, 0xe) | (0x0, 0x0, 0xf, 0xd) | (0xb, ..) => {}
+            (0x1, ..) => pending.push(nnn),
+            (0x2, ..) => pending.extend([address + 2, nnn]),
+            (0x3 | 0x4, ..) | (0x5 | 0x9, _, _, 0x0) | (0xe, _, 0x9, 0xe) | (0xe, _, 0xa, 0x1) => {
+                // A skip can land after the four-byte `ld i, long`
+                let skipped = if program.get(offset + 2..offset + 4) == Some(&[0xf0, 0x00]) { 4 } else { 2 };
+                pending.extend([address + 2, address + 2 + skipped]);
+            }
+            (0xf, 0x0, 0x0, 0x0) => pending.push(address + 4),
+            _ => pending.push(address + 2),
+        }
+    }
+
+    opcodes
+}
+
+fn is_schip(opcode: u16) -> bool {
+    matches!(
+        (opcode.bits(12..16), opcode.bits(8..12), opcode.bits(4..8), opcode.bits(0..4)),
+        (0x0, 0x0, 0xc, _) | (0x0, 0x0, 0xf, 0xb..=0xf) | (0xf, _, 0x3, 0x0) | (0xf, _, 0x7, 0x5) | (0xf, _, 0x8, 0x5)
+    )
+}
+
+fn is_xo_chip(opcode: u16) -> bool {
+    matches!(
+        (opcode.bits(12..16), opcode.bits(8..12), opcode.bits(4..8), opcode.bits(0..4)),
+        (0x5, _, _, 0x2 | 0x3) | (0xf, 0x0, 0x0, 0x0 | 0x2) | (0xf, _, 0x0, 0x1) | (0xf, _, 0x3, 0xa)
+    )
+}
@@ -1,156 +1,254 @@
-use {
-    crate::{cli::{Config, Keymap}, Error},
-    log::*,
-    sdl2::{event::Event, keyboard::{Keycode, Mod}, EventPump, Sdl},
-    std::{collections::HashMap, thread, time::Duration},
-};
-
-pub struct Input {
-    events: EventPump,
-    key_map: HashMap<Keycode, u8>,
+use {crate::cli::Keymap, log::*};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    CloseWindow,
+    SaveState,
+    LoadState,
+    Step,
+    Screenshot,
+    ToggleMute,
+    PrintRegisters,
+    CycleSpeed,
+    ToggleOverlay,
+    Redraw,
+    OpenProgram,
+}
+
+pub trait Input {
+    fn keypad(&self) -> &Keypad;
+    fn keypad_mut(&mut self) -> &mut Keypad;
+    fn process_pending_input(&mut self) -> Vec<Action>;
+
+    fn turbo_held(&self) -> bool {
+        false
+    }
+
+    fn rewind_held(&self) -> bool {
+        false
+    }
+
+    fn is_key_pressed(&self, key: u8) -> bool {
+        self.keypad().is_key_pressed(key)
+    }
+
+    fn wait_for_input(&mut self) -> Option<u8> {
+        self.keypad_mut().wait_for_input()
+    }
+}
+
+#[derive(Default)]
+pub struct NullInput {
+    keypad: Keypad,
+}
+
+impl NullInput {
+    pub fn new() -> NullInput {
+        NullInput::default()
+    }
+}
+
+impl Input for NullInput {
+    fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    fn process_pending_input(&mut self) -> Vec<Action> {
+        Vec::new()
+    }
+}
+
+// Everything that changes the keypad, in the order it happened, so a recording
+// can replay taps shorter than a poll exactly
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyEvent {
+    Down(u8),
+    Up(u8),
+    Poll,
+    ReleaseAll,
+}
+
+#[derive(Default)]
+pub struct Keypad {
     key_status: [bool; 16],
-    last_key: Option<u8>,
-    pub quit: bool,
+    pressed_this_poll: [bool; 16],
+    deferred_release: [bool; 16],
+    waiting: bool,
+    pending_key: Option<u8>,
+    released_key: Option<u8>,
+    journal: Option<Vec<KeyEvent>>,
 }
 
-impl Input {
-    pub fn new(sdl: &Sdl, config: &Config) -> Result<Input, Error> {
-        info!(target: "sdl", "creating event pump");
-        let events = sdl.event_pump()?;
-
-        let key_map = match config.keymap {
-            Keymap::Qwerty => QWERTY_KEY_MAP,
-            Keymap::Colemak => COLEMAK_KEY_MAP,
-        }.iter()
-         .cloned()
-         .collect::<HashMap<_, _>>();
-        debug!(target: "inp", "key map: {:?}", key_map);
-
-        Ok(Input {
-            events,
-            key_map,
-            key_status: [false; 16],
-            last_key: None,
-            quit: false,
-        })
-    }
-
-    pub fn process_pending_input(&mut self) {
-        debug!(target: "inp", "processing pending input");
-        while let Some(event) = self.events.poll_event() {
-            trace!(target: "evt", "processing event {:?}", event);
-
-            match event {
-                Event::KeyDown {
-                    keycode: Some(Keycode::C),
-                    keymod: Mod::LCTRLMOD,
-                    repeat: false,
-                    ..
-                } |
-                Event::Quit { .. } => {
-                    self.quit = true;
-                    break;
-                }
-                Event::KeyDown {
-                    keycode: Some(keycode),
-                    repeat: false,
-                    ..
-                } if self.key_map.contains_key(&keycode) => {
-                    // SAFETY: The value will be present in the map because the
-                    //         guard on this match arm guarantees that the key
-                    //         is present before matching.
-                    let value = *self.key_map.get(&keycode).unwrap();
-                    trace!(target: "inp", "processing key down for {:?}", keycode);
-                    self.key_down(value);
-                }
-                Event::KeyUp {
-                    keycode: Some(keycode),
-                    repeat: false,
-                    ..
-                } if self.key_map.contains_key(&keycode) => {
-                    // SAFETY: The value will be present in the map because the
-                    //         guard on this match arm guarantees that the key
-                    //         is present before matching.
-                    let value = *self.key_map.get(&keycode).unwrap();
-                    self.key_up(value);
-                }
-                _ => {}
-            }
+impl Keypad {
+    pub fn new() -> Keypad {
+        Keypad::default()
+    }
+
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        self.key_status[key as usize]
+    }
+
+    pub fn wait_for_input(&mut self) -> Option<u8> {
+        if !self.waiting {
+            debug!(target: "inp", "waiting for next input");
+            self.waiting = true;
+            self.pending_key = None;
+            self.released_key = None;
         }
+
+        let key = self.released_key.take()?;
+        trace!(target: "inp", "key {:x} pressed and released", key);
+        self.waiting = false;
+        self.pending_key = None;
+
+        Some(key)
+    }
+
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
     }
 
-    pub fn wait_for_input(&mut self) -> u8 {
-        debug!(target: "inp", "waiting for next input");
-        self.last_key = None;
+    pub fn take_journal(&mut self) -> Vec<KeyEvent> {
+        self.journal.as_mut().map(std::mem::take).unwrap_or_default()
+    }
 
-        while self.last_key.is_none() {
-            self.process_pending_input();
-            if self.quit {
-                return 0;
+    fn log(&mut self, event: KeyEvent) {
+        if let Some(journal) = &mut self.journal {
+            journal.push(event);
+        }
+    }
+
+    pub fn begin_poll(&mut self) {
+        // A poll only matters to a replay when it ends a press or a deferred release
+        if self.pressed_this_poll.contains(&true) || self.deferred_release.contains(&true) {
+            self.log(KeyEvent::Poll);
+        }
+        for key in 0..16 {
+            if self.deferred_release[key] {
+                self.key_status[key] = false;
             }
+        }
+        self.pressed_this_poll = [false; 16];
+        self.deferred_release = [false; 16];
+    }
 
-            thread::sleep(Duration::from_millis(5));
+    pub fn release_all(&mut self) {
+        debug!(target: "inp", "releasing all keys");
+        self.log(KeyEvent::ReleaseAll);
+        self.key_status = [false; 16];
+        self.deferred_release = [false; 16];
+        self.pending_key = None;
+    }
+
+    pub fn key_down(&mut self, value: u8) {
+        self.log(KeyEvent::Down(value));
+        self.key_status[value as usize] = true;
+        self.pressed_this_poll[value as usize] = true;
+        if self.waiting && self.pending_key.is_none() {
+            self.pending_key = Some(value);
         }
+    }
 
-        // SAFETY: The exit condition of the loop above is that the last_key
-        //         field is not None.
-        self.last_key.unwrap()
+    pub fn key_up(&mut self, value: u8) {
+        self.log(KeyEvent::Up(value));
+        // A tap that starts and ends within one poll stays pressed until the next
+        if self.pressed_this_poll[value as usize] {
+            self.deferred_release[value as usize] = true;
+        } else {
+            self.key_status[value as usize] = false;
+        }
+        if self.pending_key == Some(value) {
+            self.released_key = Some(value);
+        }
     }
+}
 
-    pub fn is_key_pressed(&self, key: u8) -> bool {
-        self.key_status[key as usize]
+pub type Layout = [(char, u8)];
+
+pub fn layout(keymap: &Keymap) -> &'static Layout {
+    match keymap {
+        Keymap::Qwerty => QWERTY_LAYOUT,
+        Keymap::Colemak => COLEMAK_LAYOUT,
+        Keymap::Azerty => AZERTY_LAYOUT,
     }
 }
 
-impl Input {
-    fn key_down(&mut self, value: u8) {
-        self.key_status[value as usize] = true;
-        self.last_key = Some(value);
+// Every CHIP-8 key needs exactly one binding, or some programs can't be played
+pub fn validate_bindings(values: impl IntoIterator<Item = u8>) -> Result<(), String> {
+    let mut bound = [false; 16];
+    for value in values {
+        if value >= 16 {
+            return Err(format!("{:x} is not a CHIP-8 key", value));
+        }
+        if std::mem::replace(&mut bound[value as usize], true) {
+            return Err(format!("key {:x} is bound more than once", value));
+        }
     }
 
-    fn key_up(&mut self, value: u8) {
-        self.key_status[value as usize] = false;
+    match bound.iter().position(|&bound| !bound) {
+        Some(missing) => Err(format!("key {:x} is not bound", missing)),
+        None => Ok(()),
     }
 }
 
-type KeyMapping = [(Keycode, u8)];
-
-#[allow(dead_code)]
-pub static QWERTY_KEY_MAP: &KeyMapping = &[
-    (Keycode::Num1, 0x1),
-    (Keycode::Num2, 0x2),
-    (Keycode::Num3, 0x3),
-    (Keycode::Num4, 0xc),
-    (Keycode::Q, 0x4),
-    (Keycode::W, 0x5),
-    (Keycode::E, 0x6),
-    (Keycode::R, 0xd),
-    (Keycode::A, 0x7),
-    (Keycode::S, 0x8),
-    (Keycode::D, 0x9),
-    (Keycode::F, 0xe),
-    (Keycode::Z, 0xa),
-    (Keycode::X, 0x0),
-    (Keycode::C, 0xb),
-    (Keycode::V, 0xf),
+pub static QWERTY_LAYOUT: &Layout = &[
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xc),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('e', 0x6),
+    ('r', 0xd),
+    ('a', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xe),
+    ('z', 0xa),
+    ('x', 0x0),
+    ('c', 0xb),
+    ('v', 0xf),
+];
+
+pub static COLEMAK_LAYOUT: &Layout = &[
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xc),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('f', 0x6),
+    ('p', 0xd),
+    ('a', 0x7),
+    ('r', 0x8),
+    ('s', 0x9),
+    ('t', 0xe),
+    ('z', 0xa),
+    ('x', 0x0),
+    ('c', 0xb),
+    ('v', 0xf),
 ];
 
-#[allow(dead_code)]
-pub static COLEMAK_KEY_MAP: &KeyMapping = &[
-    (Keycode::Num1, 0x1),
-    (Keycode::Num2, 0x2),
-    (Keycode::Num3, 0x3),
-    (Keycode::Num4, 0xc),
-    (Keycode::Q, 0x4),
-    (Keycode::W, 0x5),
-    (Keycode::F, 0x6),
-    (Keycode::P, 0xd),
-    (Keycode::A, 0x7),
-    (Keycode::R, 0x8),
-    (Keycode::S, 0x9),
-    (Keycode::T, 0xe),
-    (Keycode::Z, 0xa),
-    (Keycode::X, 0x0),
-    (Keycode::C, 0xb),
-    (Keycode::V, 0xf),
+pub static AZERTY_LAYOUT: &Layout = &[
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xc),
+    ('a', 0x4),
+    ('z', 0x5),
+    ('e', 0x6),
+    ('r', 0xd),
+    ('q', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xe),
+    ('w', 0xa),
+    ('x', 0x0),
+    ('c', 0xb),
+    ('v', 0xf),
 ];
@@ -0,0 +1,54 @@
+use {
+    crate::Error,
+    log::*,
+    std::{
+        fs,
+        io::{self, BufRead, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+const EXTENSIONS: [&str; 2] = ["ch8", "rom"];
+
+pub fn find_programs(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    info!(target: "cli", "looking for programs in {}", dir.display());
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        if path.is_file() && EXTENSIONS.iter().any(|known| known.eq_ignore_ascii_case(extension)) {
+            programs.push(path);
+        }
+    }
+    programs.sort();
+
+    Ok(programs)
+}
+
+pub fn choose_program(dir: &Path) -> Result<Option<PathBuf>, Error> {
+    let mut programs = find_programs(dir)?;
+    if programs.is_empty() {
+        return Err(Error::NoPrograms(dir.to_path_buf()));
+    }
+
+    for (index, program) in programs.iter().enumerate() {
+        let name = program.file_name().unwrap_or_default().to_string_lossy();
+        println!("{:>3}) {}", index + 1, name);
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("program (1-{}, or enter to cancel): ", programs.len());
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Ok(None);
+        }
+
+        match line.trim().parse::<usize>() {
+            Ok(choice) if (1..=programs.len()).contains(&choice) => return Ok(Some(programs.swap_remove(choice - 1))),
+            _ => println!("{} is not in the list", line.trim()),
+        }
+    }
+}
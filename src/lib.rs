@@ -0,0 +1,119 @@
+pub mod audio;
+pub mod chip8;
+pub mod cli;
+#[cfg(feature = "control")]
+pub mod control;
+pub mod debugger;
+pub mod disassembler;
+pub mod display;
+pub mod info;
+pub mod input;
+pub mod launcher;
+pub mod recording;
+pub mod replay;
+pub mod screenshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sdl;
+pub mod state;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub use {chip8::Chip8, state::MachineState};
+
+use std::{error, fmt::{self, Formatter}, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    IO(io::Error),
+    AddressOutOfBounds { address: usize, min: usize, max: usize },
+    Gif(gif::EncodingError),
+    InvalidKeymap(String),
+    InvalidOption(String),
+    InvalidReplay(String),
+    InvalidRom(String),
+    InvalidSaveState(String),
+    NoPrograms(PathBuf),
+    Png(png::EncodingError),
+    RomTooLarge { size: usize, max: usize },
+    #[cfg(not(target_arch = "wasm32"))]
+    SdlCall(String),
+    #[cfg(not(target_arch = "wasm32"))]
+    Sdl(sdl2::IntegerOrSdlError),
+    #[cfg(not(target_arch = "wasm32"))]
+    Tex(sdl2::render::TextureValueError),
+    #[cfg(not(target_arch = "wasm32"))]
+    Win(sdl2::video::WindowBuildError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::IO(e) => write!(f, "I/O error: {}", e),
+            Error::AddressOutOfBounds { address, min, max } => {
+                write!(f, "address {:03x} must be between {:03x} and {:03x}", address, min, max)
+            }
+            Error::Gif(e) => write!(f, "error encoding a GIF: {}", e),
+            Error::InvalidKeymap(s) => write!(f, "invalid keymap: {}", s),
+            Error::InvalidOption(s) => write!(f, "invalid option: {}", s),
+            Error::InvalidReplay(s) => write!(f, "invalid input recording: {}", s),
+            Error::InvalidRom(s) => write!(f, "invalid program: {}", s),
+            Error::InvalidSaveState(s) => write!(f, "invalid save state: {}", s),
+            Error::NoPrograms(dir) => write!(f, "no .ch8 or .rom programs in {}", dir.display()),
+            Error::Png(e) => write!(f, "error encoding a PNG: {}", e),
+            Error::RomTooLarge { size, max } => {
+                write!(f, "program is {} bytes, but only {} bytes fit in memory", size, max)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::SdlCall(s) => write!(f, "SDL error: {}", s),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::Sdl(e) => write!(f, "SDL error: {}", e),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::Tex(e) => write!(f, "error creating a texture: {}", e),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::Win(e) => write!(f, "error building a window: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(inner: io::Error) -> Error {
+        Error::IO(inner)
+    }
+}
+
+impl From<gif::EncodingError> for Error {
+    fn from(inner: gif::EncodingError) -> Error {
+        Error::Gif(inner)
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(inner: png::EncodingError) -> Error {
+        Error::Png(inner)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<sdl2::video::WindowBuildError> for Error {
+    fn from(inner: sdl2::video::WindowBuildError) -> Error {
+        Error::Win(inner)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<sdl2::IntegerOrSdlError> for Error {
+    fn from(inner: sdl2::IntegerOrSdlError) -> Error {
+        Error::Sdl(inner)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<sdl2::render::TextureValueError> for Error {
+    fn from(inner: sdl2::render::TextureValueError) -> Error {
+        Error::Tex(inner)
+    }
+}
@@ -1,67 +1,125 @@
-mod audio;
-mod chip8;
-mod cli;
-mod display;
-mod input;
-
 use {
-    chip8::Chip8,
-    clap::Clap,
-    cli::Config,
-    std::{error, fmt::{self, Formatter}, io},
+    chirp8::{
+        audio::{Audio, NullAudio},
+        chip8,
+        cli::{self, Config},
+        disassembler,
+        display::Framebuffer,
+        info::RomInfo,
+        launcher,
+        input::NullInput,
+        sdl::{SdlAudio, SdlDisplay, SdlInput},
+        terminal::{TerminalDisplay, TerminalInput},
+        Chip8, Error,
+    },
+    std::{fs, process, time::Duration},
 };
 
+const BENCHMARK_DURATION: Duration = Duration::from_secs(5);
+const EXPECT_CYCLES: u64 = 1_000_000;
+
 fn main() -> Result<(), Error> {
-    let config = Config::parse();
+    let mut config = Config::load()?;
     cli::configure_logging(config.verbose);
 
-    let mut c8 = Chip8::new(&config)?;
-    c8.run()?;
+    let path = match config.program.clone() {
+        Some(path) => path,
+        None => match launcher::choose_program(&config.rom_dir)? {
+            Some(path) => path,
+            None => return Ok(()),
+        },
+    };
+    config.program = Some(path.clone());
 
-    Ok(())
-}
+    if config.disassemble {
+        let program = chip8::read_program(&path)?;
+        for line in disassembler::disassemble_program(&program, config.load_address) {
+            println!("{}", line);
+        }
 
-#[derive(Debug)]
-pub enum Error {
-    IO(io::Error),
-    S(String),
-    Sdl(sdl2::IntegerOrSdlError),
-    Win(sdl2::video::WindowBuildError),
-}
+        return Ok(());
+    }
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
-            Error::IO(e) => write!(f, "I/O error: {}", e),
-            Error::S(s) => write!(f, "error: {}", s),
-            Error::Sdl(e) => write!(f, "SDL error: {}", e),
-            Error::Win(e) => write!(f, "error building a window: {}", e),
-        }
+    if config.rom_info {
+        let program = chip8::read_program(&path)?;
+        println!("{}", RomInfo::scan(&program, config.load_address));
+
+        return Ok(());
+    }
+
+    if config.benchmark || config.expect.is_some() {
+        config.headless = true;
     }
-}
 
-impl error::Error for Error {}
+    let mut c8 = if config.headless {
+        let audio = Box::new(NullAudio);
+        let display = Box::new(Framebuffer::new());
+        let input = Box::new(NullInput::new());
 
-impl From<io::Error> for Error {
-    fn from(inner: io::Error) -> Error {
-        Error::IO(inner)
+        Chip8::new(&config, audio, display, input)?
+    } else if config.terminal {
+        // The debugger prompt and step output can't share the raw terminal with the screen
+        if config.step || !config.breakpoints.is_empty() {
+            return Err(Error::InvalidOption("--break and --step can't be used with --terminal".into()));
+        }
+        let audio = Box::new(NullAudio);
+        let display = Box::new(TerminalDisplay::new(&config)?);
+        let input = Box::new(TerminalInput::new(&config)?);
+
+        Chip8::new(&config, audio, display, input)?
+    } else {
+        let sdl = sdl2::init().map_err(Error::SdlCall)?;
+        let audio: Box<dyn Audio> = if config.no_audio {
+            Box::new(NullAudio)
+        } else {
+            Box::new(SdlAudio::new(&sdl, &config)?)
+        };
+        let display = Box::new(SdlDisplay::new(&sdl, &config)?);
+        let input = Box::new(SdlInput::new(&sdl, &config)?);
+
+        Chip8::new(&config, audio, display, input)?
+    };
+    if config.benchmark {
+        let (cycles, elapsed) = c8.benchmark(BENCHMARK_DURATION)?;
+        println!(
+            "executed {} instructions in {:.2}s ({:.2} MIPS)",
+            cycles,
+            elapsed.as_secs_f64(),
+            cycles as f64 / elapsed.as_secs_f64() / 1_000_000.0,
+        );
+    } else if let Some(expect) = &config.expect {
+        let expected = fs::read_to_string(expect)?;
+        c8.run_cycles(config.max_cycles.unwrap_or(EXPECT_CYCLES))?;
+
+        let screen = c8.display().framebuffer();
+        if screen.matches(&expected) {
+            println!("pass: {}", path.display());
+        } else {
+            println!("fail: {} (after {} cycles)", path.display(), c8.cycles());
+            println!("expected:\n{}", expected.trim_end());
+            println!("actual:\n{}", screen.to_string().trim_end());
+            process::exit(1);
+        }
+    } else {
+        c8.run()?;
     }
-}
 
-impl From<String> for Error {
-    fn from(s: String) -> Error {
-        Error::S(s)
+    if c8.is_halted() {
+        eprintln!("machine halted after {} cycles", c8.cycles());
+        eprintln!("{}", c8.registers_string());
     }
-}
 
-impl From<sdl2::video::WindowBuildError> for Error {
-    fn from(inner: sdl2::video::WindowBuildError) -> Error {
-        Error::Win(inner)
+    if config.print_screen {
+        print!("{}", c8.display().framebuffer());
+    }
+
+    if let Some(report) = c8.coverage_report() {
+        print!("{}", report);
     }
-}
 
-impl From<sdl2::IntegerOrSdlError> for Error {
-    fn from(inner: sdl2::IntegerOrSdlError) -> Error {
-        Error::Sdl(inner)
+    if let Some((start, len)) = config.dump {
+        print!("{}", c8.dump_memory(start, len));
     }
+
+    Ok(())
 }
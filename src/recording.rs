@@ -0,0 +1,79 @@
+use {
+    crate::{
+        display::{Framebuffer, Rgb},
+        Error,
+    },
+    log::*,
+    std::{
+        fs::File,
+        io::BufWriter,
+        path::PathBuf,
+        time::{Duration, Instant},
+    },
+};
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+const FINAL_FRAME_DELAY: u16 = 100;
+
+pub struct Recorder {
+    path: PathBuf,
+    colors: [Rgb; 4],
+    scale: usize,
+    frames: Vec<(Instant, Vec<u8>)>,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf, colors: [Rgb; 4], scale: usize) -> Recorder {
+        Recorder {
+            path,
+            colors,
+            scale,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn capture(&mut self, framebuffer: &Framebuffer) {
+        let now = Instant::now();
+        if let Some((last, _)) = self.frames.last() {
+            if now.duration_since(*last) < FRAME_INTERVAL {
+                return;
+            }
+        }
+
+        self.frames.push((now, framebuffer.pixels()));
+    }
+
+    pub fn finish(mut self, framebuffer: &Framebuffer) -> Result<(), Error> {
+        let pixels = framebuffer.pixels();
+        if self.frames.last().map(|(_, last)| last) != Some(&pixels) {
+            self.frames.push((Instant::now(), pixels));
+        }
+
+        info!(target: "exe", "writing {} frames to {}", self.frames.len(), self.path.display());
+        let palette: Vec<u8> = self.colors.iter().flat_map(|&Rgb(r, g, b)| [r, g, b]).collect();
+        let width = framebuffer.w * self.scale;
+        let height = framebuffer.h * self.scale;
+
+        let file = BufWriter::new(File::create(&self.path)?);
+        let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &palette)?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        for (index, (time, pixels)) in self.frames.iter().enumerate() {
+            let mut indexed = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    indexed.push(pixels[(y / self.scale) * framebuffer.w + (x / self.scale)] & 3);
+                }
+            }
+
+            let mut frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, indexed, None);
+            frame.delay = match self.frames.get(index + 1) {
+                Some((next, _)) => (next.duration_since(*time).as_millis() / 10).max(2) as u16,
+                None => FINAL_FRAME_DELAY,
+            };
+            encoder.write_frame(&frame)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use {
+    crate::{
+        input::{KeyEvent, Keypad},
+        Error,
+    },
+    log::*,
+    std::{
+        collections::VecDeque,
+        fs::{self, File},
+        io::{BufWriter, Write},
+        path::Path,
+    },
+};
+
+// Each line of a recording is `<cycle> <key> down|up`, with the key in hex,
+// `<cycle> poll` where a poll ended a press or released a tapped key, or
+// `<cycle> release-all`, in the order the keypad saw them
+pub struct InputRecorder {
+    file: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn new(path: &Path) -> Result<InputRecorder, Error> {
+        info!(target: "inp", "recording input to {}", path.display());
+        Ok(InputRecorder {
+            file: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    // The keypad has to have its journal started for there to be anything to record
+    pub fn record(&mut self, cycle: u64, keypad: &mut Keypad) -> Result<(), Error> {
+        for event in keypad.take_journal() {
+            match event {
+                KeyEvent::Down(key) => writeln!(self.file, "{} {:x} down", cycle, key)?,
+                KeyEvent::Up(key) => writeln!(self.file, "{} {:x} up", cycle, key)?,
+                KeyEvent::Poll => writeln!(self.file, "{} poll", cycle)?,
+                KeyEvent::ReleaseAll => writeln!(self.file, "{} release-all", cycle)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush()?;
+
+        Ok(())
+    }
+}
+
+pub struct InputReplay {
+    events: VecDeque<(u64, KeyEvent)>,
+    keypad: Keypad,
+}
+
+impl InputReplay {
+    pub fn load(path: &Path) -> Result<InputReplay, Error> {
+        let mut events = VecDeque::new();
+        for (index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let invalid = || {
+                Error::InvalidReplay(format!(
+                    "{}:{}: expected `<cycle> <key> down|up`, `<cycle> poll` or `<cycle> release-all`",
+                    path.display(),
+                    index + 1,
+                ))
+            };
+            let mut fields = line.split_whitespace();
+            let cycle = fields.next().and_then(|cycle| cycle.parse().ok()).ok_or_else(invalid)?;
+            let event = match (fields.next(), fields.next()) {
+                (Some("poll"), None) => KeyEvent::Poll,
+                (Some("release-all"), None) => KeyEvent::ReleaseAll,
+                (Some(key), Some(action)) => {
+                    let key = u8::from_str_radix(key, 16).ok().filter(|&key| key < 16).ok_or_else(invalid)?;
+                    match action {
+                        "down" => KeyEvent::Down(key),
+                        "up" => KeyEvent::Up(key),
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            };
+            events.push_back((cycle, event));
+        }
+        info!(target: "inp", "replaying {} input events from {}", events.len(), path.display());
+
+        Ok(InputReplay {
+            events,
+            keypad: Keypad::new(),
+        })
+    }
+
+    pub fn advance(&mut self, cycle: u64) {
+        match self.events.front() {
+            Some(&(at, ..)) if at <= cycle => {}
+            _ => return,
+        }
+
+        // Events at a later cycle came from a later poll, and a recorded poll
+        // is repeated for polls at the same cycle or without any key events
+        self.keypad.begin_poll();
+        while let Some(&(at, event)) = self.events.front() {
+            if at > cycle {
+                break;
+            }
+            self.events.pop_front();
+            trace!(target: "inp", "replaying {:?} at cycle {}", event, at);
+            match event {
+                KeyEvent::Down(key) => self.keypad.key_down(key),
+                KeyEvent::Up(key) => self.keypad.key_up(key),
+                KeyEvent::Poll => self.keypad.begin_poll(),
+                KeyEvent::ReleaseAll => self.keypad.release_all(),
+            }
+        }
+        if self.events.is_empty() {
+            info!(target: "inp", "replay finished at cycle {}", cycle);
+        }
+    }
+
+    pub fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+}
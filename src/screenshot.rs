@@ -0,0 +1,50 @@
+use {
+    crate::{
+        display::{Framebuffer, Rgb},
+        Error,
+    },
+    std::{
+        fs::File,
+        io::BufWriter,
+        path::{Path, PathBuf},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+pub fn write_png(path: &Path, framebuffer: &Framebuffer, colors: [Rgb; 4], scale: usize) -> Result<(), Error> {
+    let width = framebuffer.w * scale;
+    let height = framebuffer.h * scale;
+
+    let mut data = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let Rgb(r, g, b) = colors[framebuffer.pixel(x / scale, y / scale) as usize];
+            data.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)?;
+
+    Ok(())
+}
+
+pub fn timestamped_path(extension: &str) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+
+    // Several screenshots in the same second get a counter instead of replacing each other
+    let mut path = PathBuf::from(format!("chirp8-{}.{}", seconds, extension));
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = PathBuf::from(format!("chirp8-{}-{}.{}", seconds, count, extension));
+    }
+
+    path
+}
@@ -0,0 +1,183 @@
+use {
+    crate::{
+        audio::{self, Audio},
+        cli::{Config, Waveform},
+        Error,
+    },
+    log::*,
+    sdl2::{audio::{AudioCallback, AudioDevice, AudioSpecDesired}, Sdl},
+    std::f32::consts::TAU,
+};
+
+const RAMP_SECONDS: f32 = 0.005;
+const PATTERN_BITS: f32 = 128.0;
+const CLICK_SECONDS: f32 = 0.01;
+const CLICK_HZ: f32 = 1500.0;
+
+struct Oscillator {
+    waveform: Waveform,
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+    gain: f32,
+    target_gain: f32,
+    gain_step: f32,
+    sample_rate: f32,
+    pattern: Option<[u8; 16]>,
+    pattern_phase: f32,
+    pattern_inc: f32,
+    click_remaining: usize,
+    click_phase: f32,
+}
+
+impl AudioCallback for Oscillator {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        // Generate the waveform, ramping the gain toward its target to avoid clicks
+        for x in out.iter_mut() {
+            if self.gain < self.target_gain {
+                self.gain = (self.gain + self.gain_step).min(self.target_gain);
+            } else if self.gain > self.target_gain {
+                self.gain = (self.gain - self.gain_step).max(self.target_gain);
+            }
+
+            let value = match &self.pattern {
+                Some(pattern) => {
+                    let bit = (self.pattern_phase * PATTERN_BITS) as usize % 128;
+                    if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                None => sample(self.waveform, self.phase),
+            };
+            // The click fades out on its own, independent of the beep's gain
+            let click = if self.click_remaining > 0 {
+                self.click_remaining -= 1;
+                let envelope = self.click_remaining as f32 / (CLICK_SECONDS * self.sample_rate);
+                sample(Waveform::Square, self.click_phase) * envelope
+            } else {
+                0.0
+            };
+
+            *x = (value * self.gain + click) * self.volume;
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+            self.pattern_phase = (self.pattern_phase + self.pattern_inc) % 1.0;
+            self.click_phase = (self.click_phase + CLICK_HZ / self.sample_rate) % 1.0;
+        }
+    }
+}
+
+pub struct SdlAudio {
+    device: AudioDevice<Oscillator>,
+    volume: f32,
+    muted: bool,
+    playing: bool,
+}
+
+impl SdlAudio {
+    pub fn new(sdl: &Sdl, config: &Config) -> Result<SdlAudio, Error> {
+        let frequency = config.beep_hz;
+        if frequency.is_nan() || frequency <= 0.0 {
+            return Err(Error::InvalidOption("beep frequency must be greater than zero".into()));
+        }
+
+        let desired_spec = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let volume = config.volume.clamp(0.0, 1.0);
+
+        let audio = sdl.audio().map_err(Error::SdlCall)?;
+        let device = audio.open_playback(None, &desired_spec, |spec| {
+            Oscillator {
+                waveform: config.waveform,
+                phase_inc: frequency / spec.freq as f32,
+                phase: 0.0,
+                volume,
+                gain: 0.0,
+                target_gain: 0.0,
+                gain_step: 1.0 / (RAMP_SECONDS * spec.freq as f32),
+                sample_rate: spec.freq as f32,
+                pattern: None,
+                pattern_phase: 0.0,
+                pattern_inc: audio::playback_rate(audio::DEFAULT_PITCH) / (PATTERN_BITS * spec.freq as f32),
+                click_remaining: 0,
+                click_phase: 0.0,
+            }
+        }).map_err(Error::SdlCall)?;
+        device.resume();
+
+        Ok(SdlAudio {
+            device,
+            volume,
+            muted: false,
+            playing: false,
+        })
+    }
+}
+
+impl Audio for SdlAudio {
+    fn start(&mut self) {
+        if !self.playing {
+            self.playing = true;
+            self.device.lock().target_gain = 1.0;
+        }
+    }
+
+    fn stop(&mut self) {
+        if self.playing {
+            self.playing = false;
+            self.device.lock().target_gain = 0.0;
+        }
+    }
+
+    fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        info!(target: "sdl", "{} audio", if self.muted { "muting" } else { "unmuting" });
+        self.device.lock().volume = if self.muted { 0.0 } else { self.volume };
+    }
+
+    fn set_pattern(&mut self, pattern: [u8; 16]) {
+        trace!(target: "sdl", "loading audio pattern {:02x?}", pattern);
+        self.device.lock().pattern = Some(pattern);
+    }
+
+    fn set_pitch(&mut self, pitch: u8) {
+        let mut oscillator = self.device.lock();
+        oscillator.pattern_inc = audio::playback_rate(pitch) / (PATTERN_BITS * oscillator.sample_rate);
+    }
+
+    fn click(&mut self) {
+        let mut oscillator = self.device.lock();
+        oscillator.click_remaining = (CLICK_SECONDS * oscillator.sample_rate) as usize;
+        oscillator.click_phase = 0.0;
+    }
+
+    fn reset(&mut self) {
+        self.stop();
+        let mut oscillator = self.device.lock();
+        oscillator.pattern = None;
+        oscillator.pattern_phase = 0.0;
+        oscillator.pattern_inc = audio::playback_rate(audio::DEFAULT_PITCH) / (PATTERN_BITS * oscillator.sample_rate);
+    }
+}
+
+fn sample(waveform: Waveform, phase: f32) -> f32 {
+    match waveform {
+        Waveform::Square => {
+            if phase <= 0.5 {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        Waveform::Sine => (phase * TAU).sin(),
+        Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+        Waveform::Sawtooth => 2.0 * phase - 1.0,
+    }
+}
@@ -0,0 +1,245 @@
+use {
+    crate::{
+        cli::Config,
+        display::{Display, Framebuffer, Rgb, SCREEN_HEIGHT_PIXELS, SCREEN_WIDTH_PIXELS},
+        screenshot, Error,
+    },
+    super::overlay,
+    log::*,
+    sdl2::{
+        hint,
+        pixels::{Color, PixelFormatEnum},
+        rect::Rect,
+        render::{BlendMode, Canvas, Texture},
+        video::Window,
+        Sdl,
+    },
+    std::time::Instant,
+};
+
+const FADE_PER_FRAME: f32 = 0.6;
+const OVERLAY_ALPHA: u8 = 192;
+
+pub struct SdlDisplay {
+    scale: usize,
+    colors: [Rgb; 4],
+    screenshot_requested: bool,
+    grid: bool,
+    fade: bool,
+    fading: bool,
+    overlay: Option<String>,
+    overlay_changed: bool,
+    shades: Vec<[f32; 3]>,
+    last_present: Instant,
+    framebuffer: Framebuffer,
+    canvas: Canvas<Window>,
+    texture: Texture,
+}
+
+impl SdlDisplay {
+    pub fn new(sdl: &Sdl, config: &Config) -> Result<SdlDisplay, Error> {
+        let scale = config.scale();
+
+        let width = SCREEN_WIDTH_PIXELS * scale;
+        let height = SCREEN_HEIGHT_PIXELS * scale;
+
+        info!(target: "sdl", "creating window at {}x scale ({}x{} pixels)", scale, width, height);
+        let video = sdl.video().map_err(Error::SdlCall)?;
+        let window = video
+            .window("CHIP-8", width as u32, height as u32)
+            .position_centered()
+            .resizable()
+            .build()?;
+        let mut canvas = window.into_canvas();
+        if config.vsync {
+            info!(target: "sdl", "enabling vsync");
+            canvas = canvas.present_vsync();
+        }
+        let canvas = canvas.build()?;
+
+        // Textures pick up the scale quality when they're created, and anything but
+        // nearest blurs the pixels whenever SDL does scale them
+        if !hint::set("SDL_RENDER_SCALE_QUALITY", "nearest") {
+            warn!(target: "sdl", "couldn't set nearest scaling; pixels may be blurry");
+        }
+        let texture = canvas.create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            SCREEN_WIDTH_PIXELS as u32,
+            SCREEN_HEIGHT_PIXELS as u32,
+        )?;
+
+        let colors = config.colors();
+        let Rgb(r, g, b) = colors[0];
+
+        Ok(SdlDisplay {
+            scale,
+            colors,
+            screenshot_requested: false,
+            grid: config.grid,
+            fade: config.fade,
+            fading: false,
+            overlay: None,
+            overlay_changed: false,
+            shades: vec![[r as f32, g as f32, b as f32]; SCREEN_WIDTH_PIXELS * SCREEN_HEIGHT_PIXELS],
+            last_present: Instant::now(),
+            framebuffer: Framebuffer::new(),
+            canvas,
+            texture,
+        })
+    }
+}
+
+impl SdlDisplay {
+    fn update_shades(&mut self) {
+        let elapsed = self.last_present.elapsed();
+        self.last_present = Instant::now();
+        let decay = FADE_PER_FRAME.powf(elapsed.as_secs_f32() * 60.0);
+
+        let Rgb(r, g, b) = self.colors[0];
+        let background = [r as f32, g as f32, b as f32];
+        self.fading = false;
+        for (shade, pixel) in self.shades.iter_mut().zip(self.framebuffer.pixels()) {
+            if pixel != 0 {
+                let Rgb(r, g, b) = self.colors[pixel as usize & 3];
+                *shade = [r as f32, g as f32, b as f32];
+                continue;
+            }
+
+            for (channel, &target) in shade.iter_mut().zip(background.iter()) {
+                *channel = target + (*channel - target) * decay;
+                if (*channel - target).abs() < 0.5 {
+                    *channel = target;
+                } else {
+                    self.fading = true;
+                }
+            }
+        }
+    }
+}
+
+impl Display for SdlDisplay {
+    fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    fn needs_presenting(&self) -> bool {
+        self.framebuffer.dirty_rect.is_some() || self.fading || self.overlay_changed
+    }
+
+    fn show_stats(&mut self, stats: &str) {
+        if let Err(e) = self.canvas.window_mut().set_title(&format!("CHIP-8 ({})", stats)) {
+            warn!(target: "sdl", "couldn't set the window title: {}", e);
+        }
+    }
+
+    fn show_overlay(&mut self, text: Option<&str>) {
+        if self.overlay.as_deref() != text {
+            self.overlay = text.map(String::from);
+            self.overlay_changed = true;
+        }
+    }
+
+    fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+        self.framebuffer.invalidate();
+    }
+
+    fn present(&mut self) -> Result<(), Error> {
+        if self.fade {
+            self.update_shades();
+        }
+
+        let framebuffer = &mut self.framebuffer;
+        let colors = self.colors;
+        if self.fade {
+            framebuffer.dirty_rect = None;
+            let (shades, w) = (&self.shades, framebuffer.w);
+            self.texture.with_lock(None, |buffer, pitch| {
+                for (index, shade) in shades.iter().enumerate() {
+                    let offset = (index / w) * pitch + (index % w) * 3;
+                    for (byte, &channel) in buffer[offset..offset + 3].iter_mut().zip(shade.iter()) {
+                        *byte = channel as u8;
+                    }
+                }
+            }).map_err(Error::SdlCall)?;
+        } else if let Some(dirty) = framebuffer.dirty_rect.take() {
+            debug!(target: "sdl", "updating texture region {:?}", dirty);
+            let region = Rect::new(dirty.x as i32, dirty.y as i32, dirty.w as u32, dirty.h as u32);
+            self.texture.with_lock(region, |buffer, pitch| {
+                for y in 0..dirty.h {
+                    for x in 0..dirty.w {
+                        let Rgb(r, g, b) = colors[framebuffer.pixel(dirty.x + x, dirty.y + y) as usize];
+
+                        let offset = y * pitch + x * 3;
+                        buffer[offset] = r;
+                        buffer[offset + 1] = g;
+                        buffer[offset + 2] = b;
+                    }
+                }
+            }).map_err(Error::SdlCall)?;
+        }
+
+        // Scale by the largest whole factor that fits and center the result
+        let (width, height) = self.canvas.output_size().map_err(Error::SdlCall)?;
+        let scale = (width / SCREEN_WIDTH_PIXELS as u32)
+            .min(height / SCREEN_HEIGHT_PIXELS as u32)
+            .max(1);
+        let (scaled_width, scaled_height) = (SCREEN_WIDTH_PIXELS as u32 * scale, SCREEN_HEIGHT_PIXELS as u32 * scale);
+        let destination = Rect::new(
+            (width as i32 - scaled_width as i32) / 2,
+            (height as i32 - scaled_height as i32) / 2,
+            scaled_width,
+            scaled_height,
+        );
+
+        trace!(target: "sdl", "copying texture to canvas at {:?}", destination);
+        self.canvas.set_draw_color(sdl_color(self.colors[0]));
+        self.canvas.clear();
+        self.canvas.copy(&self.texture, None, destination).map_err(Error::SdlCall)?;
+
+        if self.grid && scale >= 3 {
+            let columns = (1..SCREEN_WIDTH_PIXELS as i32).map(|x| {
+                Rect::new(destination.x() + x * scale as i32, destination.y(), 1, scaled_height)
+            });
+            let rows = (1..SCREEN_HEIGHT_PIXELS as i32).map(|y| {
+                Rect::new(destination.x(), destination.y() + y * scale as i32, scaled_width, 1)
+            });
+            let lines: Vec<_> = columns.chain(rows).collect();
+            self.canvas.fill_rects(&lines).map_err(Error::SdlCall)?;
+        }
+
+        if let Some(text) = &self.overlay {
+            let size = (scale as i32 / 4).max(1);
+            let (text_width, text_height) = overlay::text_size(text, size);
+            let Rgb(r, g, b) = self.colors[0];
+            self.canvas.set_blend_mode(BlendMode::Blend);
+            self.canvas.set_draw_color(Color::RGBA(r, g, b, OVERLAY_ALPHA));
+            self.canvas.fill_rect(Rect::new(0, 0, text_width + 2 * size as u32, text_height + 2 * size as u32)).map_err(Error::SdlCall)?;
+            self.canvas.set_blend_mode(BlendMode::None);
+            self.canvas.set_draw_color(sdl_color(self.colors[1]));
+            self.canvas.fill_rects(&overlay::text_rects(text, size, size, size)).map_err(Error::SdlCall)?;
+        }
+        self.overlay_changed = false;
+
+        debug!(target: "sdl", "presenting canvas");
+        self.canvas.present();
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+
+            let path = screenshot::timestamped_path("png");
+            info!(target: "sdl", "saving screenshot to {}", path.display());
+            screenshot::write_png(&path, &self.framebuffer, self.colors, self.scale)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn sdl_color(Rgb(r, g, b): Rgb) -> Color {
+    Color::RGB(r, g, b)
+}
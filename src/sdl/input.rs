@@ -0,0 +1,438 @@
+use {
+    crate::{
+        cli::Config,
+        input::{self, Action, Input, Keypad},
+        Error,
+    },
+    log::*,
+    sdl2::{
+        controller::{Button, GameController},
+        event::{Event, WindowEvent},
+        keyboard::{Keycode, Mod, Scancode},
+        EventPump, GameControllerSubsystem, Sdl,
+    },
+    std::{
+        collections::HashMap,
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+// Keys the emulator handles before the keymap, so they can't be bound to the keypad
+const HOTKEYS: &[Keycode] = &[
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F9,
+    Keycode::F12,
+    Keycode::N,
+    Keycode::M,
+    Keycode::Tab,
+    Keycode::Backspace,
+    Keycode::Escape,
+];
+
+enum Rebind {
+    Target,
+    Key(u8),
+}
+
+pub struct SdlInput {
+    events: EventPump,
+    key_map: HashMap<Keycode, u8>,
+    key_map_path: Option<PathBuf>,
+    rebind: Option<Rebind>,
+    button_map: HashMap<Button, u8>,
+    controller_subsystem: GameControllerSubsystem,
+    controllers: Vec<GameController>,
+    keypad: Keypad,
+    turbo: bool,
+    rewind: bool,
+}
+
+impl SdlInput {
+    pub fn new(sdl: &Sdl, config: &Config) -> Result<SdlInput, Error> {
+        info!(target: "sdl", "creating event pump");
+        let events = sdl.event_pump().map_err(Error::SdlCall)?;
+
+        let key_map = match &config.keymap_file {
+            Some(path) => read_key_map(path)?,
+            None => {
+                let key_map = input::layout(&config.keymap)
+                    .iter()
+                    .filter_map(|&(key, value)| Some((Keycode::from_i32(key as i32)?, value)))
+                    .collect::<HashMap<_, _>>();
+                input::validate_bindings(key_map.values().copied())
+                    .map_err(|e| Error::InvalidKeymap(format!("{:?} layout: {}", config.keymap, e)))?;
+                key_map
+            }
+        };
+        debug!(target: "inp", "key map: {:?}", key_map);
+
+        // Controllers that are already connected arrive as device-added events
+        let controller_subsystem = sdl.game_controller().map_err(Error::SdlCall)?;
+
+        Ok(SdlInput {
+            events,
+            key_map,
+            key_map_path: config.keymap_file.clone(),
+            rebind: None,
+            button_map: CONTROLLER_BUTTON_MAP.iter().cloned().collect(),
+            controller_subsystem,
+            controllers: Vec::new(),
+            keypad: Keypad::new(),
+            turbo: false,
+            rewind: false,
+        })
+    }
+}
+
+impl SdlInput {
+    fn rebind_key(&mut self, keycode: Keycode) {
+        if keycode == Keycode::Escape {
+            info!(target: "inp", "rebinding cancelled");
+            self.rebind = None;
+            return;
+        }
+
+        match self.rebind {
+            Some(Rebind::Target) => {
+                let name = keycode.name();
+                match u8::from_str_radix(&name, 16) {
+                    Ok(value) if name.len() == 1 => {
+                        info!(target: "inp", "rebinding: press the key to bind to {:x}", value);
+                        self.rebind = Some(Rebind::Key(value));
+                    }
+                    _ => warn!(target: "inp", "rebinding: {} is not a CHIP-8 key", name),
+                }
+            }
+            Some(Rebind::Key(value)) => {
+                if HOTKEYS.contains(&keycode) {
+                    warn!(target: "inp", "rebinding: {} is a hotkey; press another key", keycode.name());
+                    return;
+                }
+                self.rebind = None;
+
+                // Taking a key that's already bound swaps the two bindings, so every
+                // CHIP-8 key keeps one
+                let mut key_map = self.key_map.clone();
+                let previous = key_map.iter().find(|&(_, &bound)| bound == value).map(|(&keycode, _)| keycode);
+                if let Some(previous) = previous {
+                    key_map.remove(&previous);
+                }
+                if let (Some(displaced), Some(previous)) = (key_map.insert(keycode, value), previous) {
+                    key_map.insert(previous, displaced);
+                    info!(target: "inp", "bound {} to {:x}", previous.name(), displaced);
+                }
+                if let Err(e) = input::validate_bindings(key_map.values().copied()) {
+                    warn!(target: "inp", "rebinding: {}; keeping the old bindings", e);
+                    return;
+                }
+                self.key_map = key_map;
+                info!(target: "inp", "bound {} to {:x}", keycode.name(), value);
+
+                if let Some(path) = &self.key_map_path {
+                    if let Err(e) = write_key_map(path, &self.key_map) {
+                        warn!(target: "inp", "couldn't save key map to {}: {}", path.display(), e);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+impl Input for SdlInput {
+    fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    fn process_pending_input(&mut self) -> Vec<Action> {
+        debug!(target: "inp", "processing pending input");
+        self.keypad.begin_poll();
+        let mut actions = Vec::new();
+        while let Some(event) = self.events.poll_event() {
+            trace!(target: "evt", "processing event {:?}", event);
+
+            if let (
+                Some(_),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                },
+            ) = (&self.rebind, &event)
+            {
+                self.rebind_key(*keycode);
+                continue;
+            }
+
+            match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::C),
+                    keymod: Mod::LCTRLMOD,
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::Quit);
+                    break;
+                }
+                Event::Quit { .. } => {
+                    actions.push(Action::CloseWindow);
+                    break;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::SaveState);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::LoadState);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::N),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::Step);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::ToggleOverlay);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::CycleSpeed);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::OpenProgram);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::Screenshot);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => {
+                    self.turbo = true;
+                }
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    ..
+                } => {
+                    self.turbo = false;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    repeat: false,
+                    ..
+                } => {
+                    self.rewind = true;
+                }
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => {
+                    self.rewind = false;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    repeat: false,
+                    ..
+                } => {
+                    info!(target: "inp", "rebinding: press the CHIP-8 key (0-F) to rebind, or Escape to cancel");
+                    self.rebind = Some(Rebind::Target);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::PrintRegisters);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    repeat: false,
+                    ..
+                } => {
+                    actions.push(Action::ToggleMute);
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if self.key_map.contains_key(&keycode) => {
+                    // SAFETY: The value will be present in the map because the
+                    //         guard on this match arm guarantees that the key
+                    //         is present before matching.
+                    let value = *self.key_map.get(&keycode).unwrap();
+                    trace!(target: "inp", "processing key down for {:?}", keycode);
+                    self.keypad.key_down(value);
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if self.key_map.contains_key(&keycode) => {
+                    // SAFETY: The value will be present in the map because the
+                    //         guard on this match arm guarantees that the key
+                    //         is present before matching.
+                    let value = *self.key_map.get(&keycode).unwrap();
+                    self.keypad.key_up(value);
+                }
+                Event::Window {
+                    win_event: WindowEvent::Resized(..) | WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    actions.push(Action::Redraw);
+                }
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => {
+                    self.keypad.release_all();
+                    self.turbo = false;
+                    self.rewind = false;
+                }
+                Event::ControllerButtonDown { button, .. } => {
+                    if let Some(&value) = self.button_map.get(&button) {
+                        trace!(target: "inp", "processing button down for {:?}", button);
+                        self.keypad.key_down(value);
+                    }
+                }
+                Event::ControllerButtonUp { button, .. } => {
+                    if let Some(&value) = self.button_map.get(&button) {
+                        self.keypad.key_up(value);
+                    }
+                }
+                Event::ControllerDeviceAdded { which, .. } => match self.controller_subsystem.open(which) {
+                    Ok(controller) => {
+                        info!(target: "inp", "opened controller {}", controller.name());
+                        self.controllers.push(controller);
+                    }
+                    Err(e) => warn!(target: "inp", "couldn't open controller {}: {}", which, e),
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers.retain(|controller| controller.instance_id() != which);
+                }
+                _ => {}
+            }
+        }
+
+        let keyboard = self.events.keyboard_state();
+        for (&keycode, &value) in &self.key_map {
+            let held = Scancode::from_keycode(keycode).is_some_and(|scancode| keyboard.is_scancode_pressed(scancode));
+            if held && !self.keypad.is_key_pressed(value) {
+                trace!(target: "inp", "{:?} is held without a key down event", keycode);
+                self.keypad.key_down(value);
+            }
+        }
+
+        actions
+    }
+
+    fn turbo_held(&self) -> bool {
+        self.turbo
+    }
+
+    fn rewind_held(&self) -> bool {
+        self.rewind
+    }
+}
+
+fn read_key_map(path: &Path) -> Result<HashMap<Keycode, u8>, Error> {
+    info!(target: "inp", "reading key map from {}", path.display());
+    let contents = fs::read_to_string(path)?;
+    let mut key_map = HashMap::new();
+    let mut bound = [false; 16];
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: &str| Error::InvalidKeymap(format!("{}:{}: {}", path.display(), number + 1, message));
+        let (key, name) = line.split_once('=').ok_or_else(|| error("expected `KEY = KeyName`"))?;
+        let key = key.trim().trim_matches('"');
+        let name = name.trim().trim_matches('"');
+
+        let value = u8::from_str_radix(key, 16)
+            .ok()
+            .filter(|&value| value < 16)
+            .ok_or_else(|| error(&format!("{} is not a CHIP-8 key", key)))?;
+        let keycode = Keycode::from_name(name).ok_or_else(|| error(&format!("unknown key name {}", name)))?;
+        if HOTKEYS.contains(&keycode) {
+            return Err(error(&format!("{} is a hotkey and can't be bound", name)));
+        }
+
+        if std::mem::replace(&mut bound[value as usize], true) {
+            return Err(error(&format!("key {:x} is bound more than once", value)));
+        }
+        if key_map.insert(keycode, value).is_some() {
+            return Err(error(&format!("{} is bound to more than one key", name)));
+        }
+    }
+
+    if let Some(missing) = bound.iter().position(|&bound| !bound) {
+        return Err(Error::InvalidKeymap(format!("{}: key {:x} is not bound", path.display(), missing)));
+    }
+
+    Ok(key_map)
+}
+
+fn write_key_map(path: &Path, key_map: &HashMap<Keycode, u8>) -> Result<(), Error> {
+    let mut bindings = key_map.iter().collect::<Vec<_>>();
+    bindings.sort_by_key(|(_, &value)| value);
+
+    let mut contents = String::new();
+    for (keycode, value) in bindings {
+        contents.push_str(&format!("{:x} = \"{}\"\n", value, keycode.name()));
+    }
+    fs::write(path, contents)?;
+
+    Ok(())
+}
+
+pub static CONTROLLER_BUTTON_MAP: &[(Button, u8)] = &[
+    (Button::DPadUp, 0x2),
+    (Button::DPadLeft, 0x4),
+    (Button::DPadRight, 0x6),
+    (Button::DPadDown, 0x8),
+    (Button::A, 0x5),
+    (Button::B, 0x0),
+    (Button::X, 0x7),
+    (Button::Y, 0x9),
+    (Button::LeftShoulder, 0x1),
+    (Button::RightShoulder, 0x3),
+    (Button::Back, 0xe),
+    (Button::Start, 0xf),
+];
@@ -0,0 +1,6 @@
+mod audio;
+mod display;
+mod input;
+mod overlay;
+
+pub use self::{audio::SdlAudio, display::SdlDisplay, input::SdlInput};
@@ -0,0 +1,61 @@
+use sdl2::rect::Rect;
+
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+
+// Rects for the lit pixels of the text, with one glyph pixel `size` window
+// pixels square and a column of spacing between glyphs
+pub fn text_rects(text: &str, x: i32, y: i32, size: i32) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let top = y + line_index as i32 * (GLYPH_HEIGHT + 1) * size;
+        for (index, c) in line.chars().enumerate() {
+            let left = x + index as i32 * (GLYPH_WIDTH + 1) * size;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> column) != 0 {
+                        rects.push(Rect::new(left + column * size, top + row as i32 * size, size as u32, size as u32));
+                    }
+                }
+            }
+        }
+    }
+
+    rects
+}
+
+pub fn text_size(text: &str, size: i32) -> (u32, u32) {
+    let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as i32;
+    let rows = text.lines().count() as i32;
+    (
+        ((columns * (GLYPH_WIDTH + 1) - 1).max(0) * size) as u32,
+        ((rows * (GLYPH_HEIGHT + 1) - 1).max(0) * size) as u32,
+    )
+}
+
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_lowercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' | 's' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b100, 0b100],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'i' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'p' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        't' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'v' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        _ => [0; 5],
+    }
+}
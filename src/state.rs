@@ -0,0 +1,151 @@
+use {
+    crate::{
+        display::{SCREEN_HEIGHT_PIXELS, SCREEN_WIDTH_PIXELS},
+        Error,
+    },
+    std::io::{self, Read},
+};
+
+const SAVE_STATE_MAGIC: &[u8; 4] = b"C8SS";
+const SAVE_STATE_VERSION: u8 = 2;
+// Lengths in a state are checked against these before anything is allocated
+const MAX_MEMORY: usize = 0x10000;
+const MAX_PIXELS: usize = SCREEN_WIDTH_PIXELS * SCREEN_HEIGHT_PIXELS;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MachineState {
+    pub v: [u8; 16],
+    pub i: usize,
+    pub pc: usize,
+    pub sp: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub halted: bool,
+    pub cycles: u64,
+    pub memory: Vec<u8>,
+    pub pixels: Vec<u8>,
+    pub planes: u8,
+    pub audio_pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+}
+
+impl MachineState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64 + self.memory.len() + self.pixels.len());
+        bytes.extend_from_slice(SAVE_STATE_MAGIC);
+        bytes.push(SAVE_STATE_VERSION);
+        bytes.extend_from_slice(&self.v);
+        bytes.extend_from_slice(&(self.i as u16).to_be_bytes());
+        bytes.extend_from_slice(&(self.pc as u16).to_be_bytes());
+        bytes.extend_from_slice(&(self.sp as u16).to_be_bytes());
+        bytes.push(self.sound_timer);
+        bytes.push(self.delay_timer);
+        bytes.push(self.halted as u8);
+        bytes.extend_from_slice(&self.cycles.to_be_bytes());
+        bytes.extend_from_slice(&(self.memory.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.memory);
+        bytes.extend_from_slice(&(self.pixels.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.pixels);
+        bytes.push(self.planes);
+        bytes.push(self.pitch);
+        match &self.audio_pattern {
+            Some(pattern) => {
+                bytes.push(1);
+                bytes.extend_from_slice(pattern);
+            }
+            None => bytes.push(0),
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<MachineState, Error> {
+        let mut magic = [0; 4];
+        bytes.read_exact(&mut magic)?;
+        if &magic != SAVE_STATE_MAGIC {
+            return Err(Error::InvalidSaveState("not a save state".into()));
+        }
+
+        let version = read_u8(&mut bytes)?;
+        if version != SAVE_STATE_VERSION {
+            return Err(Error::InvalidSaveState(format!("unsupported version {}", version)));
+        }
+
+        let mut v = [0; 16];
+        bytes.read_exact(&mut v)?;
+        let i = read_u16(&mut bytes)? as usize;
+        let pc = read_u16(&mut bytes)? as usize;
+        let sp = read_u16(&mut bytes)? as usize;
+        let sound_timer = read_u8(&mut bytes)?;
+        let delay_timer = read_u8(&mut bytes)?;
+        let halted = read_u8(&mut bytes)? != 0;
+        let cycles = read_u64(&mut bytes)?;
+
+        let mut memory = vec![0; read_len(&mut bytes, MAX_MEMORY, "bytes of memory")?];
+        bytes.read_exact(&mut memory)?;
+
+        let mut pixels = vec![0; read_len(&mut bytes, MAX_PIXELS, "pixels")?];
+        bytes.read_exact(&mut pixels)?;
+
+        let planes = read_u8(&mut bytes)?;
+        let pitch = read_u8(&mut bytes)?;
+        let audio_pattern = match read_u8(&mut bytes)? {
+            0 => None,
+            _ => {
+                let mut pattern = [0; 16];
+                bytes.read_exact(&mut pattern)?;
+                Some(pattern)
+            }
+        };
+
+        Ok(MachineState {
+            v,
+            i,
+            pc,
+            sp,
+            delay_timer,
+            sound_timer,
+            halted,
+            cycles,
+            memory,
+            pixels,
+            planes,
+            audio_pattern,
+            pitch,
+        })
+    }
+}
+
+fn read_len(reader: &mut impl Read, max: usize, what: &str) -> Result<usize, Error> {
+    let len = read_u32(reader)? as usize;
+    if len > max {
+        return Err(Error::InvalidSaveState(format!("machine state has {} {}, at most {} fit", len, what, max)));
+    }
+
+    Ok(len)
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
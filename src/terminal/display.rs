@@ -0,0 +1,87 @@
+use {
+    crate::{
+        cli::Config,
+        display::{Display, Framebuffer, Rgb},
+        Error,
+    },
+    crossterm::{
+        cursor::{Hide, Show},
+        execute,
+        terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    log::*,
+    std::io::{self, Write},
+};
+
+pub struct TerminalDisplay {
+    colors: [Rgb; 4],
+    stats: Option<String>,
+    framebuffer: Framebuffer,
+}
+
+impl TerminalDisplay {
+    pub fn new(config: &Config) -> Result<TerminalDisplay, Error> {
+        info!(target: "dsp", "switching the terminal to the alternate screen");
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+
+        Ok(TerminalDisplay {
+            colors: config.colors(),
+            stats: None,
+            framebuffer: Framebuffer::new(),
+        })
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+
+    fn show_stats(&mut self, stats: &str) {
+        self.stats = Some(stats.to_string());
+        self.framebuffer.invalidate();
+    }
+
+    fn present(&mut self) -> Result<(), Error> {
+        self.framebuffer.dirty_rect = None;
+
+        // Each character cell shows two pixels, the top one in the foreground color of
+        // an upper half block and the bottom one in the background color
+        let Rgb(br, bg, bb) = self.colors[0];
+        let mut screen = String::from("\x1b[H");
+        for y in (0..self.framebuffer.h).step_by(2) {
+            let mut current = None;
+            for x in 0..self.framebuffer.w {
+                let cell = (self.framebuffer.pixel(x, y), self.framebuffer.pixel(x, y + 1));
+                if current != Some(cell) {
+                    let Rgb(tr, tg, tb) = self.colors[cell.0 as usize];
+                    let Rgb(lr, lg, lb) = self.colors[cell.1 as usize];
+                    screen.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m", tr, tg, tb, lr, lg, lb));
+                    current = Some(cell);
+                }
+                screen.push('▀');
+            }
+            // The terminal is in raw mode, so each line needs an explicit carriage return
+            screen.push_str(&format!("\x1b[48;2;{};{};{}m\x1b[K\r\n", br, bg, bb));
+        }
+        if let Some(stats) = &self.stats {
+            screen.push_str(&format!("\x1b[0m{}\x1b[K", stats));
+        }
+
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "{}\x1b[0m", screen)?;
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for TerminalDisplay {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+    }
+}
@@ -0,0 +1,118 @@
+use {
+    crate::{
+        cli::Config,
+        input::{self, Action, Input, Keypad},
+        Error,
+    },
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        terminal,
+    },
+    log::*,
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    },
+};
+
+// Terminals only report key presses, so a press holds the key for a short while
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+pub struct TerminalInput {
+    key_map: HashMap<char, u8>,
+    keypad: Keypad,
+    held_until: [Option<Instant>; 16],
+}
+
+impl TerminalInput {
+    pub fn new(config: &Config) -> Result<TerminalInput, Error> {
+        if config.keymap_file.is_some() {
+            warn!(target: "inp", "keymap files aren't supported in the terminal; using the {:?} layout", config.keymap);
+        }
+
+        let layout = input::layout(&config.keymap);
+        input::validate_bindings(layout.iter().map(|&(_, value)| value))
+            .map_err(|e| Error::InvalidKeymap(format!("{:?} layout: {}", config.keymap, e)))?;
+
+        info!(target: "inp", "switching the terminal to raw mode");
+        terminal::enable_raw_mode()?;
+
+        Ok(TerminalInput {
+            key_map: layout.iter().cloned().collect(),
+            keypad: Keypad::new(),
+            held_until: [None; 16],
+        })
+    }
+}
+
+impl Input for TerminalInput {
+    fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    fn process_pending_input(&mut self) -> Vec<Action> {
+        debug!(target: "inp", "processing pending input");
+        let now = Instant::now();
+        for (key, held_until) in self.held_until.iter_mut().enumerate() {
+            if held_until.is_some_and(|until| until <= now) {
+                *held_until = None;
+                self.keypad.key_up(key as u8);
+            }
+        }
+        self.keypad.begin_poll();
+
+        let mut actions = Vec::new();
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            let event = match event::read() {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!(target: "inp", "couldn't read terminal event: {}", e);
+                    break;
+                }
+            };
+            trace!(target: "evt", "processing event {:?}", event);
+
+            let (code, modifiers) = match event {
+                Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press | KeyEventKind::Repeat,
+                    ..
+                }) => (code, modifiers),
+                _ => continue,
+            };
+
+            match code {
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    actions.push(Action::Quit);
+                    break;
+                }
+                KeyCode::F(5) => actions.push(Action::SaveState),
+                KeyCode::F(9) => actions.push(Action::LoadState),
+                KeyCode::Char('n') => actions.push(Action::Step),
+                KeyCode::Char(c) => {
+                    if let Some(&value) = self.key_map.get(&c.to_ascii_lowercase()) {
+                        trace!(target: "inp", "processing key press for {:?}", c);
+                        if self.held_until[value as usize].is_none() {
+                            self.keypad.key_down(value);
+                        }
+                        self.held_until[value as usize] = Some(now + KEY_HOLD);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        actions
+    }
+}
+
+impl Drop for TerminalInput {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
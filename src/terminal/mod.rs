@@ -0,0 +1,4 @@
+mod display;
+mod input;
+
+pub use self::{display::TerminalDisplay, input::TerminalInput};
@@ -0,0 +1,91 @@
+use {
+    crate::{
+        audio::NullAudio,
+        chip8::Chip8,
+        cli::Config,
+        display::Framebuffer,
+        input::NullInput,
+    },
+    clap::Clap,
+    log::*,
+    wasm_bindgen::prelude::*,
+};
+
+#[wasm_bindgen]
+pub struct WasmChip8 {
+    chip8: Chip8,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl WasmChip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(program: &[u8], args: &str) -> Result<WasmChip8, JsValue> {
+        // The program comes from JS, so the path is only there to satisfy the parser
+        let args = ["chirp8", "--headless"].iter().copied().chain(args.split_whitespace()).chain(["program"]);
+        let config = Config::try_parse_from(args).map_err(js_error)?;
+
+        let audio = Box::new(NullAudio);
+        let display = Box::new(Framebuffer::new());
+        let input = Box::new(NullInput::new());
+        let chip8 = Chip8::with_program(&config, program, audio, display, input).map_err(js_error)?;
+
+        Ok(WasmChip8 {
+            chip8,
+            pixels: Vec::new(),
+        })
+    }
+
+    pub fn step(&mut self, cycles: u32) -> Result<(), JsValue> {
+        for _ in 0..cycles {
+            self.chip8.cycle().map_err(js_error)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        if key < 16 {
+            self.chip8.keypad_mut().key_down(key);
+        } else {
+            warn!(target: "inp", "{:x} is not a CHIP-8 key", key);
+        }
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        if key < 16 {
+            self.chip8.keypad_mut().key_up(key);
+        } else {
+            warn!(target: "inp", "{:x} is not a CHIP-8 key", key);
+        }
+    }
+
+    pub fn framebuffer_ptr(&mut self) -> *const u8 {
+        self.pixels = self.chip8.display().pixels();
+        self.pixels.as_ptr()
+    }
+
+    pub fn framebuffer_len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.chip8.display().dimensions().0
+    }
+
+    pub fn height(&self) -> usize {
+        self.chip8.display().dimensions().1
+    }
+
+    pub fn sound_playing(&self) -> bool {
+        self.chip8.sound_timer() > 0
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.chip8.is_halted()
+    }
+}
+
+fn js_error(e: impl ToString) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
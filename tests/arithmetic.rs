@@ -0,0 +1,90 @@
+mod common;
+
+use chirp8::Chip8;
+
+fn run(program: &[u8], args: &[&str]) -> Chip8 {
+    let mut c8 = common::machine(program, args);
+    for _ in 0..program.len() / 2 {
+        c8.step().unwrap();
+    }
+
+    c8
+}
+
+fn v0_and_vf(program: &[u8], args: &[&str]) -> (u8, u8) {
+    let c8 = run(program, args);
+    (c8.registers()[0], c8.registers()[15])
+}
+
+#[test]
+fn add_sets_carry() {
+    // ld v0, ff; ld v1, 01; add v0, v1
+    assert_eq!(v0_and_vf(&[0x60, 0xff, 0x61, 0x01, 0x80, 0x14], &[]), (0x00, 1));
+    assert_eq!(v0_and_vf(&[0x60, 0xfe, 0x61, 0x01, 0x80, 0x14], &[]), (0xff, 0));
+    assert_eq!(v0_and_vf(&[0x60, 0xff, 0x61, 0xff, 0x80, 0x14], &[]), (0xfe, 1));
+}
+
+#[test]
+fn sub_clears_vf_on_borrow() {
+    // ld v0, 00; ld v1, 01; sub v0, v1
+    assert_eq!(v0_and_vf(&[0x60, 0x00, 0x61, 0x01, 0x80, 0x15], &[]), (0xff, 0));
+    assert_eq!(v0_and_vf(&[0x60, 0x05, 0x61, 0x03, 0x80, 0x15], &[]), (0x02, 1));
+    assert_eq!(v0_and_vf(&[0x60, 0x03, 0x61, 0x03, 0x80, 0x15], &[]), (0x00, 1));
+}
+
+#[test]
+fn subn_clears_vf_on_borrow() {
+    // ld v0, 01; ld v1, 00; subn v0, v1
+    assert_eq!(v0_and_vf(&[0x60, 0x01, 0x61, 0x00, 0x80, 0x17], &[]), (0xff, 0));
+    assert_eq!(v0_and_vf(&[0x60, 0x01, 0x61, 0x03, 0x80, 0x17], &[]), (0x02, 1));
+    assert_eq!(v0_and_vf(&[0x60, 0x03, 0x61, 0x03, 0x80, 0x17], &[]), (0x00, 1));
+}
+
+#[test]
+fn shr_sets_vf_to_shifted_out_bit() {
+    // ld v0, 03; shr v0
+    assert_eq!(v0_and_vf(&[0x60, 0x03, 0x80, 0x06], &[]), (0x01, 1));
+    assert_eq!(v0_and_vf(&[0x60, 0x02, 0x80, 0x06], &[]), (0x01, 0));
+    assert_eq!(v0_and_vf(&[0x60, 0x00, 0x80, 0x06], &[]), (0x00, 0));
+}
+
+#[test]
+fn shl_sets_vf_to_shifted_out_bit() {
+    // ld v0, 81; shl v0
+    assert_eq!(v0_and_vf(&[0x60, 0x81, 0x80, 0x0e], &[]), (0x02, 1));
+    assert_eq!(v0_and_vf(&[0x60, 0x40, 0x80, 0x0e], &[]), (0x80, 0));
+    assert_eq!(v0_and_vf(&[0x60, 0xff, 0x80, 0x0e], &[]), (0xfe, 1));
+}
+
+#[test]
+fn shifts_read_vy_with_the_vy_quirk() {
+    // ld v0, 00; ld v1, 81; shr v0, v1 / shl v0, v1
+    let args = ["--shift-quirk", "vy"];
+    assert_eq!(v0_and_vf(&[0x60, 0x00, 0x61, 0x81, 0x80, 0x16], &args), (0x40, 1));
+    assert_eq!(v0_and_vf(&[0x60, 0x00, 0x61, 0x81, 0x80, 0x1e], &args), (0x02, 1));
+}
+
+#[test]
+fn flag_result_wins_when_vf_is_the_destination() {
+    // ld vf, ff; ld v1, 01; add vf, v1
+    let c8 = run(&[0x6f, 0xff, 0x61, 0x01, 0x8f, 0x14], &[]);
+    assert_eq!(c8.registers()[15], 1);
+}
+
+#[test]
+fn logic_ops_reset_vf_with_the_logic_quirk() {
+    // ld vf, 05; ld v0, 0c; ld v1, 0a; or v0, v1 / and v0, v1 / xor v0, v1
+    let args = ["--logic-quirk"];
+    assert_eq!(v0_and_vf(&[0x6f, 0x05, 0x60, 0x0c, 0x61, 0x0a, 0x80, 0x11], &args), (0x0e, 0));
+    assert_eq!(v0_and_vf(&[0x6f, 0x05, 0x60, 0x0c, 0x61, 0x0a, 0x80, 0x12], &args), (0x08, 0));
+    assert_eq!(v0_and_vf(&[0x6f, 0x05, 0x60, 0x0c, 0x61, 0x0a, 0x80, 0x13], &args), (0x06, 0));
+    assert_eq!(v0_and_vf(&[0x6f, 0x05, 0x60, 0x0c, 0x61, 0x0a, 0x80, 0x11], &[]), (0x0e, 5));
+}
+
+#[test]
+fn add_i_wraps_at_the_end_of_memory() {
+    // ld i, fff; ld v0, 02; add i, v0
+    let program = [0xaf, 0xff, 0x60, 0x02, 0xf0, 0x1e];
+    assert_eq!(run(&program, &[]).i(), 0x001);
+    assert_eq!(run(&program, &["--profile", "xochip"]).i(), 0x1001);
+}
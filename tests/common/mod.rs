@@ -0,0 +1,15 @@
+use {
+    chirp8::{audio::NullAudio, cli::Config, display::Framebuffer, input::NullInput, Chip8},
+    clap::Clap,
+};
+
+// A headless machine with the program loaded, configured by the given flags
+pub fn machine(program: &[u8], args: &[&str]) -> Chip8 {
+    let args = ["chirp8", "--headless"].iter().chain(args).chain(&["test.ch8"]);
+    let config = Config::parse_from(args);
+    let audio = Box::new(NullAudio);
+    let display = Box::new(Framebuffer::new());
+    let input = Box::new(NullInput::new());
+
+    Chip8::with_program(&config, program, audio, display, input).unwrap()
+}
@@ -0,0 +1,17 @@
+use {
+    chirp8::{cli::Config, display::Rgb},
+    std::{env, fs},
+};
+
+#[test]
+fn config_file_colors_keep_their_hash() {
+    let path = env::temp_dir().join(format!("chirp8-config-{}.toml", std::process::id()));
+    fs::write(&path, "# colors\nfg = \"#00ff00\"  # green\nbg = \"#102030\"\n").unwrap();
+
+    let config = Config::load_from(&path, vec!["chirp8".into(), "--bg".into(), "#000000".into(), "test.ch8".into()]);
+    fs::remove_file(&path).unwrap();
+
+    let config = config.unwrap();
+    assert_eq!(config.fg, Some(Rgb(0x00, 0xff, 0x00)));
+    assert_eq!(config.bg, Some(Rgb(0x00, 0x00, 0x00)));
+}
@@ -0,0 +1,29 @@
+mod common;
+
+const CYCLES: u64 = 20000;
+
+fn check(name: &str) {
+    let program = std::fs::read(format!("example_programs/{}.ch8", name)).unwrap();
+    let expected = std::fs::read_to_string(format!("tests/fixtures/{}.txt", name)).unwrap();
+
+    let mut c8 = common::machine(&program, &["--seed", "1"]);
+    c8.run_cycles(CYCLES).unwrap();
+
+    let screen = c8.display().framebuffer();
+    assert!(screen.matches(&expected), "{} screen after {} cycles:\n{}", name, CYCLES, screen);
+}
+
+#[test]
+fn cavern() {
+    check("cavern");
+}
+
+#[test]
+fn chipquarium() {
+    check("chipquarium");
+}
+
+#[test]
+fn countdown() {
+    check("countdown");
+}
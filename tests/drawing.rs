@@ -0,0 +1,35 @@
+mod common;
+
+#[test]
+fn sprites_clip_or_wrap_at_the_edges() {
+    // ld v0, 3e; ld v1, 1e; ld v2, 00; ld f, v2; drw v0, v1, 5
+    let program = [0x60, 0x3e, 0x61, 0x1e, 0x62, 0x00, 0xf2, 0x29, 0xd0, 0x15];
+
+    for (args, wraps) in [(&[][..], false), (&["--wrap-sprites"][..], true), (&["--profile", "xochip"][..], true)] {
+        let mut c8 = common::machine(&program, args);
+        for _ in 0..program.len() / 2 {
+            c8.step().unwrap();
+        }
+
+        let screen = c8.display().framebuffer();
+        assert_eq!(screen.pixel(63, 30), 1, "{:?}", args);
+        // The top row of the 0 runs off the right edge, and its third row off the bottom
+        assert_eq!(screen.pixel(0, 30) == 1, wraps, "{:?}", args);
+        assert_eq!(screen.pixel(62, 0) == 1, wraps, "{:?}", args);
+    }
+}
+
+#[test]
+fn sprites_read_past_the_end_of_memory_wrap_around() {
+    // ld i, fff; drw v0, v0, 2 reads 0xfff and then the top of the 0 at 0x000
+    let program = [0xaf, 0xff, 0xd0, 0x02];
+    let mut c8 = common::machine(&program, &[]);
+    for _ in 0..program.len() / 2 {
+        c8.step().unwrap();
+    }
+
+    assert!(!c8.is_halted());
+    let screen = c8.display().framebuffer();
+    assert_eq!(screen.pixel(0, 0), 0);
+    assert_eq!(screen.pixel(0, 1), 1);
+}
@@ -0,0 +1,16 @@
+mod common;
+
+#[test]
+fn spin_loop_halts_once_it_has_run_for_a_while() {
+    // ld v0, 01; jp 202
+    let program = [0x60, 0x01, 0x12, 0x02];
+
+    let mut c8 = common::machine(&program, &["--loop-detect"]);
+    c8.run_cycles(1000).unwrap();
+    assert!(c8.is_halted());
+    assert_eq!(c8.cycles(), 121);
+
+    let mut c8 = common::machine(&program, &[]);
+    c8.run_cycles(1000).unwrap();
+    assert!(!c8.is_halted());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use {
+    chirp8::{input::Keypad, replay::InputRecorder},
+    std::{env, fs},
+};
+
+#[test]
+fn replayed_keys_reach_the_program() {
+    let path = env::temp_dir().join(format!("chirp8-replay-{}.txt", std::process::id()));
+    fs::write(&path, "5 7 down\n10 7 up\n").unwrap();
+
+    // ld v0, k; jp 202
+    let mut c8 = common::machine(&[0xf0, 0x0a, 0x12, 0x02], &["--replay-input", path.to_str().unwrap()]);
+    c8.run_cycles(9).unwrap();
+    assert_eq!(c8.pc(), 0x200);
+    c8.run_cycles(10).unwrap();
+    assert_eq!(c8.pc(), 0x202);
+    assert_eq!(c8.registers()[0], 7);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn tap_within_one_poll_replays_at_the_same_cycle() {
+    let path = env::temp_dir().join(format!("chirp8-tap-{}.txt", std::process::id()));
+    let program = [0xf0, 0x0a, 0x12, 0x02];
+
+    // Key 7 goes down and up between the same two cycles while ld v0, k waits
+    let tap = |keypad: &mut Keypad| {
+        keypad.begin_poll();
+        keypad.key_down(7);
+        keypad.key_up(7);
+    };
+
+    let mut live = common::machine(&program, &[]);
+    live.run_cycles(5).unwrap();
+    tap(live.keypad_mut());
+    live.run_cycles(1).unwrap();
+    assert_eq!(live.pc(), 0x202);
+
+    let mut keypad = Keypad::new();
+    keypad.start_journal();
+    let mut recorder = InputRecorder::new(&path).unwrap();
+    tap(&mut keypad);
+    recorder.record(5, &mut keypad).unwrap();
+    keypad.begin_poll();
+    recorder.record(8, &mut keypad).unwrap();
+    recorder.flush().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "5 7 down\n5 7 up\n8 poll\n");
+
+    let mut replay = common::machine(&program, &["--replay-input", path.to_str().unwrap()]);
+    replay.run_cycles(5).unwrap();
+    assert_eq!(replay.pc(), 0x200);
+    replay.run_cycles(1).unwrap();
+    assert_eq!(replay.pc(), live.pc());
+    assert_eq!(replay.registers()[0], 7);
+
+    fs::remove_file(&path).unwrap();
+}
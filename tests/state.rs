@@ -0,0 +1,46 @@
+mod common;
+
+use chirp8::MachineState;
+
+#[test]
+fn restore_rejects_out_of_range_registers() {
+    let mut c8 = common::machine(&[0x12, 0x00], &[]);
+
+    let mut state = c8.machine_state();
+    state.i = 0x1000;
+    assert!(c8.restore_machine_state(state).is_err());
+
+    let mut state = c8.machine_state();
+    state.sp = 0x1ff;
+    assert!(c8.restore_machine_state(state).is_err());
+
+    let state = c8.machine_state();
+    assert!(c8.restore_machine_state(state).is_ok());
+}
+
+#[test]
+fn state_keeps_xo_chip_planes_and_audio() {
+    // ld i, 208; ld [pattern]; plane 2; jp 206; pattern...
+    let mut program = vec![0xa2, 0x08, 0xf0, 0x02, 0xf2, 0x01, 0x12, 0x06];
+    program.extend(0x10..0x20);
+    let mut c8 = common::machine(&program, &["--xo-chip"]);
+    for _ in 0..3 {
+        c8.step().unwrap();
+    }
+
+    let state = MachineState::from_bytes(&c8.save_state()).unwrap();
+    assert_eq!(state.planes, 2);
+    assert_eq!(state.audio_pattern, Some(std::array::from_fn(|index| 0x10 + index as u8)));
+    assert_eq!(state, c8.machine_state());
+}
+
+#[test]
+fn state_with_huge_lengths_is_rejected() {
+    let c8 = common::machine(&[0x12, 0x00], &[]);
+    let mut bytes = c8.save_state();
+    // The memory length follows the header, registers, timers, halt flag and cycle count
+    let offset = 4 + 1 + 16 + 6 + 3 + 8;
+    bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    let error = MachineState::from_bytes(&bytes).unwrap_err();
+    assert!(error.to_string().contains("at most"), "{}", error);
+}